@@ -1,3 +1,5 @@
+use std::net::SocketAddr;
+
 use async_nats::jetstream;
 use axum::{routing::get, Router};
 use flux_lib::error::Error;
@@ -5,27 +7,45 @@ use settings::AppSettings;
 use state::AppState;
 use tracing::info;
 
+mod admin;
+mod attachments;
 mod auth;
 mod error;
+mod hydration;
 mod locale;
+mod markdown;
 mod messages;
 mod notify;
+mod pagination;
 mod pushes;
+mod rpc;
 mod settings;
 mod state;
 mod streams;
 mod user;
+mod users;
+mod validation;
 
 pub async fn run() -> Result<(), Error> {
     let settings = AppSettings::new()?;
     let state = AppState::new(settings).await?;
 
     messaging(&state).await?;
+    auth::keys(&state).await?;
     http(&state).await?;
 
     Ok(())
 }
 
+pub async fn import(args: &[String]) -> Result<(), Error> {
+    let settings = AppSettings::new()?;
+    let state = admin::ImportState::new(&settings).await?;
+
+    admin::cli(&state, args).await?;
+
+    Ok(())
+}
+
 async fn http(state: &AppState) -> Result<(), Error> {
     let router = Router::new()
         .nest(
@@ -34,22 +54,31 @@ async fn http(state: &AppState) -> Result<(), Error> {
                 .route("/healthz", get(|| async {}))
                 .nest("/auth", auth::router())
                 .nest("/streams", streams::router())
+                .nest("/users", users::router())
                 .nest("/messages", messages::router())
+                .nest("/attachments", attachments::router())
                 .nest("/pushes", pushes::router())
-                .nest("/notify", notify::router()),
+                .nest("/notify", notify::router())
+                .nest("/admin", admin::router()),
         )
         .with_state(state.to_owned());
 
     let listener = tokio::net::TcpListener::bind(&state.settings.http.endpoint).await?;
 
     info!("app: started on {}", listener.local_addr()?);
-    axum::serve(listener, router).await?;
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
 async fn messaging(state: &AppState) -> Result<(), Error> {
-    notify::messaging(&state).await.unwrap();
+    notify::messaging(state).await.unwrap();
+    messages::messaging(state).await?;
+    auth::messaging(state).await?;
 
     info!("messaging: started");
 
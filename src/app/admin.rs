@@ -0,0 +1,264 @@
+use std::{collections::HashMap, io};
+
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use flux_lib::error::Error;
+use futures::{future, TryStreamExt as _};
+use serde_json::Value;
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+use self::import::state::{Import, Source, Start};
+
+use super::{
+    error::{AppError, FieldError},
+    state::AppState,
+    user::AppUser,
+};
+
+mod import;
+pub(super) mod settings;
+
+pub use import::{state::AdminImports, ImportState};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/import", post(create_import))
+        .route("/import/{import_id}", get(get_import))
+        .route("/import/{import_id}/resume", post(resume_import))
+        // The size limit is enforced while reading the file, see `create_import`
+        .layer(DefaultBodyLimit::disable())
+}
+
+// Admins are listed in the settings, there is no admin role in the users service
+fn admin(state: &AppState, user: &AppUser) -> Result<(), AppError> {
+    if !state.settings.admin.user_ids.contains(&user.id) {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(())
+}
+
+async fn create_import(
+    State(state): State<AppState>,
+    user: AppUser,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<import_response::Response>), AppError> {
+    admin(&state, &user)?;
+
+    let import_id = Uuid::now_v7();
+    let mut source = None;
+    let mut emails = HashMap::new();
+    let mut file = false;
+
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name() {
+            Some("source") => {
+                source = Some(
+                    serde_json::from_value::<Source>(Value::String(field.text().await?)).map_err(
+                        |_| {
+                            AppError::Validation(vec![FieldError::new(
+                                "source",
+                                "must be one of slack, telegram",
+                            )])
+                        },
+                    )?,
+                );
+            }
+            Some("emails") => {
+                emails = serde_json::from_str(&field.text().await?).map_err(|_| {
+                    AppError::Validation(vec![FieldError::new(
+                        "emails",
+                        "must be an object of author ids to e-mails",
+                    )])
+                })?;
+            }
+            Some("file") => {
+                upload(&state, import_id, &mut field).await?;
+                file = true;
+            }
+            _ => {}
+        }
+    }
+
+    let mut errors = vec![];
+
+    if source.is_none() {
+        errors.push(FieldError::new("source", "is required"));
+    }
+
+    if !file {
+        errors.push(FieldError::new("file", "is required"));
+    }
+
+    let Some(source) = source.filter(|_| file) else {
+        if file {
+            state.imports.delete_file(import_id).await?;
+        }
+
+        return Err(AppError::Validation(errors));
+    };
+
+    state.imports.create(import_id, source, emails).await?;
+
+    start(state, import_id).await
+}
+
+// The file goes to the object store chunk by chunk, the size limit is checked along the way
+async fn upload(
+    state: &AppState,
+    import_id: Uuid,
+    field: &mut axum::extract::multipart::Field<'_>,
+) -> Result<(), AppError> {
+    let max_size = state.settings.admin.import.max_size;
+    let mut size = 0;
+
+    let res = {
+        let mut file = StreamReader::new(field.map_err(io::Error::other).and_then(|chunk| {
+            size += chunk.len();
+
+            future::ready(if size > max_size {
+                Err(io::Error::other("file is too large"))
+            } else {
+                Ok(chunk)
+            })
+        }));
+
+        state.imports.put_file(import_id, &mut file).await
+    };
+
+    // The upload was cut short, so whatever was stored is only removed on a best-effort basis
+    if size > max_size {
+        let _ = state.imports.delete_file(import_id).await;
+
+        return Err(AppError::Validation(vec![FieldError::new(
+            "file",
+            format!("must be at most {} bytes", max_size),
+        )]));
+    }
+
+    Ok(res?)
+}
+
+async fn start(
+    state: AppState,
+    import_id: Uuid,
+) -> Result<(StatusCode, Json<import_response::Response>), AppError> {
+    match state.imports.start(import_id).await? {
+        Start::Started(import) => {
+            tokio::spawn(import::run(ImportState::from(&state), import.clone()));
+
+            Ok((StatusCode::ACCEPTED, Json(import.into())))
+        }
+        Start::Running => Err(AppError::Conflict),
+        Start::NotFound => Err(AppError::NoEntity),
+    }
+}
+
+async fn get_import(
+    Path(import_id): Path<Uuid>,
+    State(state): State<AppState>,
+    user: AppUser,
+) -> Result<Json<import_response::Response>, AppError> {
+    admin(&state, &user)?;
+
+    let import = state
+        .imports
+        .get(import_id)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    Ok(Json(import.into()))
+}
+
+// Messages created by earlier runs are skipped, so an interrupted or partially failed
+// import can be run again from its stored file
+async fn resume_import(
+    Path(import_id): Path<Uuid>,
+    State(state): State<AppState>,
+    user: AppUser,
+) -> Result<(StatusCode, Json<import_response::Response>), AppError> {
+    admin(&state, &user)?;
+
+    start(state, import_id).await
+}
+
+mod import_response {
+    use serde::Serialize;
+
+    use super::import::state::{Failure, Import, ImportStatus, Source};
+
+    #[derive(Serialize)]
+    pub struct Response {
+        import_id: String,
+        source: Source,
+        status: ImportStatus,
+        total: usize,
+        imported: usize,
+        failures: Vec<Failure>,
+        error: Option<String>,
+    }
+
+    impl From<Import> for Response {
+        fn from(import: Import) -> Self {
+            Self {
+                import_id: import.import_id.into(),
+                source: import.source,
+                status: import.status,
+                total: import.total,
+                imported: import.imported,
+                failures: import.failures,
+                error: import.error,
+            }
+        }
+    }
+}
+
+// `flux-gw import <slack|telegram> <file> [emails.json]` or `flux-gw import resume <import_id>`
+pub async fn cli(state: &ImportState, args: &[String]) -> Result<(), Error> {
+    let ImportState { imports, .. } = state;
+
+    let import_id: Uuid = match args {
+        [command, import_id] if command == "resume" => import_id.parse()?,
+        [source, path, emails @ ..] if emails.len() <= 1 => {
+            let source: Source = serde_json::from_value(Value::String(source.clone()))?;
+
+            let emails = match emails.first() {
+                Some(emails) => serde_json::from_slice(&tokio::fs::read(emails).await?)?,
+                None => HashMap::new(),
+            };
+
+            let import_id = Uuid::now_v7();
+
+            imports
+                .put_file(import_id, &mut tokio::fs::File::open(path).await?)
+                .await?;
+
+            imports.create(import_id, source, emails).await?.import_id
+        }
+        _ => {
+            return Err(Error::msg(
+                "usage: flux-gw import <slack|telegram> <file> [emails.json] | flux-gw import resume <import_id>",
+            ))
+        }
+    };
+
+    let import: Import = match imports.start(import_id).await? {
+        Start::Started(import) => import,
+        Start::Running => return Err(Error::msg(format!("import {} is running", import_id))),
+        Start::NotFound => return Err(Error::msg(format!("import {} is not found", import_id))),
+    };
+
+    let import = import::run(state.clone(), import).await;
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&import_response::Response::from(import))?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap, io::BufReader};
+
+use async_nats::jetstream;
+use flux_lib::error::Error;
+use flux_messages_api::{messages_service_client::MessagesServiceClient, CreateMessageRequest};
+use flux_users_api::{users_service_client::UsersServiceClient, GetUsersByEmailsRequest};
+use tonic::transport::Channel;
+use tracing::error;
+
+use crate::app::{settings::AppSettings, state::AppState};
+
+use super::settings::ImportSettings;
+
+use state::{AdminImports, Failure, Import, ImportStatus, Imported, Source};
+
+mod slack;
+pub(super) mod state;
+mod telegram;
+
+// Messages of an export in their original order, thread roots always come before their replies
+pub struct Export {
+    pub emails: HashMap<String, String>,
+    pub items: Vec<Item>,
+}
+
+pub struct Item {
+    pub source_id: String,
+    pub parent_id: Option<String>,
+    pub author: String,
+    pub text: String,
+}
+
+// Only what a run needs, so the CLI can import next to a running gateway without taking its
+// resources, such as the lock on the search index
+#[derive(Clone)]
+pub struct ImportState {
+    pub settings: ImportSettings,
+    pub users_service_client: UsersServiceClient<Channel>,
+    pub messages_service_client: MessagesServiceClient<Channel>,
+    pub imports: AdminImports,
+}
+
+impl ImportState {
+    pub async fn new(settings: &AppSettings) -> Result<Self, Error> {
+        let nats = async_nats::connect(&settings.nats.endpoint).await?;
+        let js = jetstream::new(nats);
+
+        Ok(Self {
+            settings: settings.admin.import.clone(),
+            users_service_client: AppState::users_service_client(
+                settings.clients.flux_users.endpoint.clone(),
+            )
+            .await?,
+            messages_service_client: AppState::messages_service_client(
+                settings.clients.flux_messages.endpoint.clone(),
+            )
+            .await?,
+            imports: AdminImports::new(&js, &settings.admin.import).await?,
+        })
+    }
+}
+
+impl From<&AppState> for ImportState {
+    fn from(state: &AppState) -> Self {
+        Self {
+            settings: state.settings.admin.import.clone(),
+            users_service_client: state.users_service_client.clone(),
+            messages_service_client: state.messages_service_client.clone(),
+            imports: state.imports.clone(),
+        }
+    }
+}
+
+// The import must be started with `AdminImports::start`, which marks it as running
+pub async fn run(state: ImportState, mut import: Import) -> Import {
+    (import.status, import.error) = match execute(&state, &mut import).await {
+        Ok(()) => (ImportStatus::Done, None),
+        Err(err) => {
+            error!("{}", err);
+
+            (ImportStatus::Failed, Some(err.to_string()))
+        }
+    };
+
+    if let Err(err) = state.imports.put(&mut import).await {
+        error!("{}", err);
+    }
+
+    import
+}
+
+async fn execute(state: &ImportState, import: &mut Import) -> Result<(), Error> {
+    let ImportState {
+        users_service_client,
+        imports,
+        settings,
+        ..
+    } = state;
+
+    let file = imports.get_file(import).await?;
+    let source = import.source;
+
+    // Parsing reads the whole export, so it is kept off the runtime's worker threads
+    let export = tokio::task::spawn_blocking(move || -> Result<Export, Error> {
+        let file = BufReader::new(file);
+
+        match source {
+            Source::Slack => slack::parse(file),
+            Source::Telegram => telegram::parse(file),
+        }
+    })
+    .await??;
+
+    // E-mails given with the import take precedence over the ones found in the export
+    let mut emails = export.emails;
+    emails.extend(import.emails.clone());
+
+    let get_users_by_emails_response = users_service_client
+        .clone()
+        .get_users_by_emails(GetUsersByEmailsRequest {
+            emails: emails.values().cloned().collect(),
+        })
+        .await?
+        .into_inner();
+
+    let user_ids: HashMap<String, String> = get_users_by_emails_response
+        .users
+        .into_iter()
+        .map(|user| (user.email().to_lowercase(), user.user_id().to_string()))
+        .collect();
+
+    let authors: HashMap<String, String> = emails
+        .into_iter()
+        .filter_map(|(author, email)| {
+            user_ids
+                .get(&email.to_lowercase())
+                .map(|user_id| (author, user_id.clone()))
+        })
+        .collect();
+
+    import.total = export.items.len();
+    import.imported = 0;
+
+    for (i, item) in export.items.iter().enumerate() {
+        match self::item(state, import, item, &authors).await {
+            Ok(()) => import.imported += 1,
+            Err(err) => import.failures.push(Failure {
+                source_id: item.source_id.clone(),
+                error: err.to_string(),
+            }),
+        }
+
+        if (i + 1) % settings.interval.get() == 0 {
+            imports.put(import).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn item(
+    state: &ImportState,
+    import: &Import,
+    item: &Item,
+    authors: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let ImportState {
+        messages_service_client,
+        imports,
+        settings,
+        ..
+    } = state;
+
+    let code = format!("{}-{}", import.import_id, item.source_id);
+
+    // Messages created by an earlier run of the same import are skipped. A pending record means
+    // that run died while creating the message, it is left for a manual check by its code
+    match imports.get_imported(import, &item.source_id).await? {
+        Some(Imported::Created(_)) => return Ok(()),
+        Some(Imported::Pending) => {
+            return Err(Error::msg(format!(
+                "message {} may have been created by an interrupted run",
+                code
+            )))
+        }
+        None => {}
+    }
+
+    let user_id = authors
+        .get(&item.author)
+        .ok_or_else(|| Error::msg(format!("no user for author {}", item.author)))?;
+
+    let message_id = match &item.parent_id {
+        Some(parent_id) => match imports.get_imported(import, parent_id).await? {
+            Some(Imported::Created(message_id)) => Some(message_id),
+            _ => {
+                return Err(Error::msg(format!(
+                    "thread root {} is not imported",
+                    parent_id
+                )))
+            }
+        },
+        None => None,
+    };
+
+    imports.put_pending(import, &item.source_id).await?;
+
+    let create_message_response = match messages_service_client
+        .clone()
+        .create_message(CreateMessageRequest {
+            text: Some(item.text.clone()),
+            message_id,
+            code: Some(code),
+            user_id: Some(user_id.clone()),
+            locale: Some(settings.locale.clone()),
+            attachment_ids: vec![],
+        })
+        .await
+    {
+        Ok(res) => res.into_inner(),
+        // Only a refusal proves the message was not created, otherwise the record stays pending
+        Err(status) => {
+            if !matches!(
+                status.code(),
+                tonic::Code::DeadlineExceeded | tonic::Code::Cancelled | tonic::Code::Unknown
+            ) {
+                imports.delete_pending(import, &item.source_id).await?;
+            }
+
+            return Err(status.into());
+        }
+    };
+
+    imports
+        .put_message_id(
+            import,
+            &item.source_id,
+            create_message_response.message_id(),
+        )
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Seek},
+};
+
+use flux_lib::error::Error;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use super::{Export, Item};
+
+#[derive(Deserialize)]
+struct User {
+    id: String,
+    #[serde(default)]
+    profile: Profile,
+}
+
+#[derive(Deserialize, Default)]
+struct Profile {
+    email: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(rename = "type")]
+    kind: String,
+    subtype: Option<String>,
+    user: Option<String>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+    thread_ts: Option<String>,
+}
+
+// Slack exports hold `users.json` and the messages of every channel as `{channel}/{date}.json`
+pub fn parse(file: impl Read + Seek) -> Result<Export, Error> {
+    let mut archive = ZipArchive::new(file)?;
+    let mut emails = HashMap::new();
+    let mut items = vec![];
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+
+        if !name.ends_with(".json") {
+            continue;
+        }
+
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+
+        match name.split_once('/') {
+            None if name == "users.json" => {
+                for user in serde_json::from_slice::<Vec<User>>(&data)? {
+                    if let Some(email) = user.profile.email {
+                        emails.insert(user.id, email);
+                    }
+                }
+            }
+            Some((channel, _)) => {
+                for message in serde_json::from_slice::<Vec<Message>>(&data)? {
+                    // Joins, topic changes and bot posts carry a subtype and are not imported
+                    if message.kind != "message" || message.subtype.is_some() {
+                        continue;
+                    }
+
+                    let Some(user) = message.user else {
+                        continue;
+                    };
+
+                    items.push((
+                        ts(&message.ts),
+                        Item {
+                            source_id: format!("{}/{}", channel, message.ts),
+                            parent_id: message
+                                .thread_ts
+                                .filter(|thread_ts| *thread_ts != message.ts)
+                                .map(|thread_ts| format!("{}/{}", channel, thread_ts)),
+                            author: user,
+                            text: message.text,
+                        },
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    items.sort_by_key(|(ts, _)| *ts);
+
+    Ok(Export {
+        emails,
+        items: items.into_iter().map(|(_, item)| item).collect(),
+    })
+}
+
+// `ts` is `{seconds}.{microseconds}`, compared as numbers to keep the original order
+fn ts(ts: &str) -> (u64, u64) {
+    let (seconds, microseconds) = ts.split_once('.').unwrap_or((ts, "0"));
+
+    (
+        seconds.parse().unwrap_or_default(),
+        microseconds.parse().unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write as _};
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use super::*;
+
+    fn archive(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+
+        for (name, data) in files {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(data.as_bytes()).unwrap();
+        }
+
+        let mut file = zip.finish().unwrap();
+        file.set_position(0);
+
+        file
+    }
+
+    #[test]
+    fn parse_users() {
+        let export = parse(archive(&[(
+            "users.json",
+            r#"[{"id": "U1", "profile": {"email": "ada@example.com"}}, {"id": "U2", "profile": {}}]"#,
+        )]))
+        .unwrap();
+
+        assert_eq!(
+            export.emails,
+            HashMap::from([("U1".to_string(), "ada@example.com".to_string())])
+        );
+    }
+
+    #[test]
+    fn parse_threads_in_order() {
+        let export = parse(archive(&[
+            (
+                "general/2024-01-02.json",
+                r#"[
+                    {"type": "message", "user": "U2", "text": "reply", "ts": "1700000010.000001", "thread_ts": "1700000009.000002"}
+                ]"#,
+            ),
+            (
+                "general/2024-01-01.json",
+                r#"[
+                    {"type": "message", "user": "U1", "text": "root", "ts": "1700000009.000002", "thread_ts": "1700000009.000002"},
+                    {"type": "message", "user": "U1", "text": "first", "ts": "1700000009.000001"}
+                ]"#,
+            ),
+        ]))
+        .unwrap();
+
+        let items: Vec<(&str, Option<&str>, &str)> = export
+            .items
+            .iter()
+            .map(|item| {
+                (
+                    item.source_id.as_str(),
+                    item.parent_id.as_deref(),
+                    item.text.as_str(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            items,
+            vec![
+                ("general/1700000009.000001", None, "first"),
+                ("general/1700000009.000002", None, "root"),
+                (
+                    "general/1700000010.000001",
+                    Some("general/1700000009.000002"),
+                    "reply"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_skips_subtypes() {
+        let export = parse(archive(&[(
+            "general/2024-01-01.json",
+            r#"[
+                {"type": "message", "subtype": "channel_join", "user": "U1", "text": "joined", "ts": "1.1"},
+                {"type": "message", "subtype": "bot_message", "text": "bot", "ts": "1.2"},
+                {"type": "message", "text": "no user", "ts": "1.3"},
+                {"type": "message", "user": "U1", "text": "hi", "ts": "1.4"}
+            ]"#,
+        )]))
+        .unwrap();
+
+        assert_eq!(export.items.len(), 1);
+        assert_eq!(export.items[0].text, "hi");
+        assert_eq!(export.items[0].author, "U1");
+    }
+}
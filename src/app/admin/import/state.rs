@@ -0,0 +1,255 @@
+use std::{collections::HashMap, fs};
+
+use async_nats::jetstream::{kv, object_store};
+use flux_lib::error::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::io::{self, AsyncRead, AsyncSeekExt as _};
+use uuid::Uuid;
+
+use crate::app::{auth::sessions, AppJS};
+
+use super::super::settings::ImportSettings;
+
+#[derive(Clone)]
+pub struct AdminImports {
+    store: kv::Store,
+    files: object_store::ObjectStore,
+    lease: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Import {
+    pub import_id: Uuid,
+    pub source: Source,
+    pub status: ImportStatus,
+    pub emails: HashMap<String, String>,
+    pub total: usize,
+    pub imported: usize,
+    pub failures: Vec<Failure>,
+    pub error: Option<String>,
+    // Refreshed by every write of a running import, a stale one belongs to a dead run
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Slack,
+    Telegram,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Failure {
+    pub source_id: String,
+    pub error: String,
+}
+
+pub enum Start {
+    Started(Import),
+    Running,
+    NotFound,
+}
+
+// A pending record is written before the message is created, so a run that died in between
+// is not imported twice
+pub enum Imported {
+    Pending,
+    Created(String),
+}
+
+impl AdminImports {
+    pub async fn new(js: &AppJS, settings: &ImportSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        let files = js
+            .create_object_store(object_store::Config {
+                bucket: settings.files.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            store,
+            files,
+            lease: settings.lease,
+        })
+    }
+
+    // The file is stored first, see `put_file`
+    pub async fn create(
+        &self,
+        import_id: Uuid,
+        source: Source,
+        emails: HashMap<String, String>,
+    ) -> Result<Import, Error> {
+        let mut import = Import {
+            import_id,
+            source,
+            status: ImportStatus::Pending,
+            emails,
+            total: 0,
+            imported: 0,
+            failures: vec![],
+            error: None,
+            updated_at: 0,
+        };
+
+        self.put(&mut import).await?;
+
+        Ok(import)
+    }
+
+    // Runs are exclusive, the status is switched with the revision it was read at
+    pub async fn start(&self, import_id: Uuid) -> Result<Start, Error> {
+        let Some(entry) = self.store.entry(key(import_id)).await? else {
+            return Ok(Start::NotFound);
+        };
+
+        let mut import: Import = serde_json::from_slice(&entry.value)?;
+        let now = sessions::now();
+
+        if import.status == ImportStatus::Running && import.updated_at + self.lease > now {
+            return Ok(Start::Running);
+        }
+
+        import.status = ImportStatus::Running;
+        import.failures.clear();
+        import.error = None;
+        import.updated_at = now;
+
+        Ok(
+            match self
+                .store
+                .update(
+                    key(import_id),
+                    serde_json::to_vec(&import)?.into(),
+                    entry.revision,
+                )
+                .await
+            {
+                Ok(_) => Start::Started(import),
+                Err(_) => Start::Running,
+            },
+        )
+    }
+
+    pub async fn get(&self, import_id: Uuid) -> Result<Option<Import>, Error> {
+        match self.store.get(key(import_id)).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn put(&self, import: &mut Import) -> Result<(), Error> {
+        import.updated_at = sessions::now();
+
+        self.store
+            .put(key(import.import_id), serde_json::to_vec(import)?.into())
+            .await?;
+
+        Ok(())
+    }
+
+    // The uploaded file is streamed into the object store and kept, so an interrupted import
+    // can be resumed from it
+    pub async fn put_file(
+        &self,
+        import_id: Uuid,
+        file: &mut (impl AsyncRead + Unpin),
+    ) -> Result<(), Error> {
+        self.files.put(import_id.to_string().as_str(), file).await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_file(&self, import_id: Uuid) -> Result<(), Error> {
+        self.files.delete(import_id.to_string()).await?;
+
+        Ok(())
+    }
+
+    // Spooled to an unnamed temporary file, which is removed once closed, so a large export is
+    // never held in memory
+    pub async fn get_file(&self, import: &Import) -> Result<fs::File, Error> {
+        let mut object = self.files.get(import.import_id.to_string()).await?;
+        let mut file =
+            tokio::fs::File::from_std(tokio::task::spawn_blocking(tempfile::tempfile).await??);
+
+        io::copy(&mut object, &mut file).await?;
+        file.rewind().await?;
+
+        Ok(file.into_std().await)
+    }
+
+    // Created messages are recorded one by one, these records are what makes a run resumable
+    pub async fn get_imported(
+        &self,
+        import: &Import,
+        source_id: &str,
+    ) -> Result<Option<Imported>, Error> {
+        match self.store.get(message_key(import, source_id)).await? {
+            Some(value) if value.is_empty() => Ok(Some(Imported::Pending)),
+            Some(value) => Ok(Some(Imported::Created(String::from_utf8(value.to_vec())?))),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn put_pending(&self, import: &Import, source_id: &str) -> Result<(), Error> {
+        self.store
+            .put(message_key(import, source_id), Default::default())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_pending(&self, import: &Import, source_id: &str) -> Result<(), Error> {
+        self.store.delete(message_key(import, source_id)).await?;
+
+        Ok(())
+    }
+
+    pub async fn put_message_id(
+        &self,
+        import: &Import,
+        source_id: &str,
+        message_id: &str,
+    ) -> Result<(), Error> {
+        self.store
+            .put(
+                message_key(import, source_id),
+                message_id.to_string().into_bytes().into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+fn key(import_id: Uuid) -> String {
+    import_id.to_string()
+}
+
+// Source ids contain characters that are not valid in KV keys, so they are hashed
+fn message_key(import: &Import, source_id: &str) -> String {
+    format!(
+        "{}.messages.{}",
+        import.import_id,
+        hex::encode(Sha256::digest(source_id.as_bytes()))
+    )
+}
@@ -0,0 +1,159 @@
+use std::{collections::HashMap, io::Read};
+
+use flux_lib::error::Error;
+use serde::Deserialize;
+
+use super::{Export, Item};
+
+#[derive(Deserialize)]
+struct Chat {
+    messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    id: i64,
+    #[serde(rename = "type")]
+    kind: String,
+    from_id: Option<String>,
+    #[serde(default)]
+    text: Text,
+    reply_to_message_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Rich(Vec<Entity>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entity {
+    Plain(String),
+    Typed { text: String },
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self::Plain(String::new())
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        match text {
+            Text::Plain(text) => text,
+            Text::Rich(entities) => entities
+                .into_iter()
+                .map(|entity| match entity {
+                    Entity::Plain(text) | Entity::Typed { text } => text,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Telegram exports carry no e-mails, authors are mapped by the e-mails given with the import
+pub fn parse(file: impl Read) -> Result<Export, Error> {
+    let mut chat: Chat = serde_json::from_reader(file)?;
+    chat.messages.sort_by_key(|message| message.id);
+
+    let mut roots: HashMap<i64, i64> = HashMap::new();
+    let mut items = vec![];
+
+    for message in chat.messages {
+        if message.kind != "message" {
+            continue;
+        }
+
+        let Some(author) = message.from_id else {
+            continue;
+        };
+
+        // Replies form chains in Telegram, so each one joins the thread of the first message
+        let root = message
+            .reply_to_message_id
+            .and_then(|reply_to_message_id| roots.get(&reply_to_message_id).copied());
+
+        roots.insert(message.id, root.unwrap_or(message.id));
+
+        items.push(Item {
+            source_id: message.id.to_string(),
+            parent_id: root.map(|root| root.to_string()),
+            author,
+            text: message.text.into(),
+        });
+    }
+
+    Ok(Export {
+        emails: HashMap::new(),
+        items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(json: &str) -> Vec<(String, Option<String>, String, String)> {
+        parse(json.as_bytes())
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|item| (item.source_id, item.parent_id, item.author, item.text))
+            .collect()
+    }
+
+    #[test]
+    fn parse_reply_chains() {
+        let items = items(
+            r#"{"messages": [
+                {"id": 3, "type": "message", "from_id": "user1", "text": "third", "reply_to_message_id": 2},
+                {"id": 1, "type": "message", "from_id": "user1", "text": "first"},
+                {"id": 2, "type": "message", "from_id": "user2", "text": "second", "reply_to_message_id": 1},
+                {"id": 4, "type": "message", "from_id": "user2", "text": "fourth"}
+            ]}"#,
+        );
+
+        assert_eq!(
+            items,
+            vec![
+                ("1".into(), None, "user1".into(), "first".into()),
+                (
+                    "2".into(),
+                    Some("1".into()),
+                    "user2".into(),
+                    "second".into()
+                ),
+                ("3".into(), Some("1".into()), "user1".into(), "third".into()),
+                ("4".into(), None, "user2".into(), "fourth".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_skips_service_messages() {
+        let items = items(
+            r#"{"messages": [
+                {"id": 1, "type": "service", "actor_id": "user1", "action": "create_group"},
+                {"id": 2, "type": "message", "text": "no author"},
+                {"id": 3, "type": "message", "from_id": "user1", "text": "hi", "reply_to_message_id": 1}
+            ]}"#,
+        );
+
+        assert_eq!(items, vec![("3".into(), None, "user1".into(), "hi".into())]);
+    }
+
+    #[test]
+    fn parse_rich_text() {
+        let items = items(
+            r#"{"messages": [
+                {"id": 1, "type": "message", "from_id": "user1", "text": ["see ", {"type": "link", "text": "flux.app"}, "!"]}
+            ]}"#,
+        );
+
+        assert_eq!(items[0].3, "see flux.app!");
+    }
+}
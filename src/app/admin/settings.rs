@@ -0,0 +1,20 @@
+use std::num::NonZeroUsize;
+
+use serde::Deserialize;
+use uuid::Uuid;
+
+#[derive(Deserialize, Clone)]
+pub struct AdminSettings {
+    pub user_ids: Vec<Uuid>,
+    pub import: ImportSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ImportSettings {
+    pub bucket: String,
+    pub files: String,
+    pub max_size: usize,
+    pub locale: String,
+    pub interval: NonZeroUsize,
+    pub lease: u64,
+}
@@ -0,0 +1,199 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+use self::state::Variant;
+
+use super::{
+    error::{AppError, FieldError},
+    messages::find_message,
+    state::AppState,
+    user::{AppUser, Scope},
+};
+
+pub(super) mod settings;
+pub(super) mod state;
+mod storage;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/",
+            post(create_attachment).layer(Extension(Scope::MessagesWrite)),
+        )
+        .route(
+            "/{attachment_id}",
+            get(get_attachment).layer(Extension(Scope::MessagesRead)),
+        )
+        .route("/{attachment_id}/{variant}", get(download_attachment))
+        // The size limit is enforced while reading the file, see `create_attachment`
+        .layer(DefaultBodyLimit::disable())
+}
+
+async fn create_attachment(
+    State(AppState { attachments, .. }): State<AppState>,
+    user: AppUser,
+    mut multipart: Multipart,
+) -> Result<Json<create_attachment::Response>, AppError> {
+    while let Some(mut field) = multipart.next_field().await? {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let name = field.file_name().unwrap_or("file").to_string();
+        let content_type = field.content_type().map(|v| v.to_string());
+        let mut data = vec![];
+
+        while let Some(chunk) = field.chunk().await? {
+            if data.len() + chunk.len() > attachments.settings.max_size {
+                return Err(AppError::Validation(vec![FieldError::new(
+                    "file",
+                    format!("must be at most {} bytes", attachments.settings.max_size),
+                )]));
+            }
+
+            data.extend_from_slice(&chunk);
+        }
+
+        let attachment = attachments
+            .create(user.id, name, content_type, data)
+            .await?;
+
+        return Ok(Json(create_attachment::Response {
+            attachment: attachments.urls(&attachment).await?,
+        }));
+    }
+
+    Err(AppError::Validation(vec![FieldError::new(
+        "file",
+        "is required",
+    )]))
+}
+
+mod create_attachment {
+    use serde::Serialize;
+
+    use super::state::AttachmentUrls;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub attachment: AttachmentUrls,
+    }
+}
+
+// Attachment ids are time-ordered, so URLs are only issued to those who can see the message
+async fn get_attachment(
+    Path(attachment_id): Path<Uuid>,
+    State(AppState {
+        attachments,
+        messages_service_client,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<get_attachment::Response>, AppError> {
+    let attachment = attachments
+        .get(attachment_id)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    if attachment.user_id != user.id {
+        let message_id = attachment.message_id.ok_or(AppError::NoEntity)?;
+
+        find_message(&messages_service_client, message_id, &user).await?;
+    }
+
+    Ok(Json(get_attachment::Response {
+        attachment: attachments.urls(&attachment).await?,
+    }))
+}
+
+mod get_attachment {
+    use serde::Serialize;
+
+    use super::state::AttachmentUrls;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub attachment: AttachmentUrls,
+    }
+}
+
+// Serves signed URLs of the local storage, S3 URLs point to the bucket directly
+async fn download_attachment(
+    Path((attachment_id, variant)): Path<(Uuid, Variant)>,
+    State(AppState { attachments, .. }): State<AppState>,
+    Query(req): Query<download_attachment::Request>,
+) -> Result<impl IntoResponse, AppError> {
+    let key = state::key(attachment_id, variant);
+
+    if !attachments
+        .storage
+        .verify(&key, req.expires, &req.signature)
+    {
+        return Err(AppError::Forbidden);
+    }
+
+    let attachment = attachments
+        .get(attachment_id)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    let content_type = match variant {
+        Variant::Thumbnail => "image/jpeg".to_string(),
+        Variant::File => attachment.mime_type,
+    };
+
+    let data = attachments.storage.get(&key).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&attachment.name),
+            ),
+        ],
+        data,
+    ))
+}
+
+// Header values must be ASCII, the original name goes to the RFC 5987 `filename*`
+fn content_disposition(name: &str) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+
+    let encoded: String = name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+
+    format!(
+        "inline; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+mod download_attachment {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Request {
+        pub expires: u64,
+        pub signature: String,
+    }
+}
@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct AttachmentsSettings {
+    pub bucket: String,
+    pub max_size: usize,
+    pub mime_types: Vec<String>,
+    pub url_ttl: u64,
+    pub thumbnail_size: u32,
+    pub storage: StorageSettings,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum StorageSettings {
+    Local {
+        path: String,
+        base_url: String,
+        secret: String,
+    },
+    S3 {
+        endpoint: String,
+        region: String,
+        bucket: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
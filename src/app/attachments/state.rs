@@ -0,0 +1,260 @@
+use std::{collections::HashMap, io::Cursor, sync::Arc, time::Duration};
+
+use async_nats::jetstream::kv;
+use flux_lib::error::Error;
+use image::{codecs::jpeg::JpegEncoder, GenericImageView as _};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::app::{
+    error::{AppError, FieldError},
+    AppJS,
+};
+
+use super::{
+    settings::AttachmentsSettings,
+    storage::{self, Storage},
+};
+
+#[derive(Clone)]
+pub struct AttachmentsState {
+    pub settings: AttachmentsSettings,
+    pub storage: Arc<dyn Storage>,
+    store: kv::Store,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub attachment_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub thumbnail: bool,
+    // Set once the attachment is posted, readers must be able to see this message
+    #[serde(default)]
+    pub message_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    File,
+    Thumbnail,
+}
+
+impl Variant {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Variant::File => "file",
+            Variant::Thumbnail => "thumbnail",
+        }
+    }
+}
+
+// Signed URLs are issued on every read, so they never outlive `url_ttl`
+#[derive(Serialize, Clone)]
+pub struct AttachmentUrls {
+    pub attachment_id: String,
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub url: String,
+    pub thumbnail_url: Option<String>,
+}
+
+impl AttachmentsState {
+    pub async fn new(js: &AppJS, settings: AttachmentsSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        let storage = storage::new(&settings.storage).await?;
+
+        Ok(Self {
+            settings,
+            storage,
+            store,
+        })
+    }
+
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        name: String,
+        content_type: Option<String>,
+        data: Vec<u8>,
+    ) -> Result<Attachment, AppError> {
+        // The sniffed type wins over the declared one, which is only used for text formats
+        let mime_type = infer::get(&data)
+            .map(|kind| kind.mime_type().to_string())
+            .or_else(|| {
+                content_type.filter(|content_type| {
+                    content_type.starts_with("text/") && std::str::from_utf8(&data).is_ok()
+                })
+            })
+            .unwrap_or_else(|| "application/octet-stream".into());
+
+        if !self.settings.mime_types.contains(&mime_type) {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "file",
+                format!("type {} is not allowed", mime_type),
+            )]));
+        }
+
+        let mut attachment = Attachment {
+            attachment_id: Uuid::now_v7(),
+            user_id,
+            name,
+            mime_type,
+            size: data.len(),
+            width: None,
+            height: None,
+            thumbnail: false,
+            message_id: None,
+        };
+
+        if attachment.mime_type.starts_with("image/") {
+            let (width, height, thumbnail) = thumbnail(data.clone(), self.settings.thumbnail_size)
+                .await
+                .map_err(|_| {
+                    AppError::Validation(vec![FieldError::new("file", "must be a valid image")])
+                })?;
+
+            self.storage
+                .put(
+                    &key(attachment.attachment_id, Variant::Thumbnail),
+                    "image/jpeg",
+                    thumbnail,
+                )
+                .await?;
+
+            attachment.width = Some(width);
+            attachment.height = Some(height);
+            attachment.thumbnail = true;
+        }
+
+        self.storage
+            .put(
+                &key(attachment.attachment_id, Variant::File),
+                &attachment.mime_type,
+                data,
+            )
+            .await?;
+
+        self.put(&attachment).await?;
+
+        Ok(attachment)
+    }
+
+    pub async fn attach(&self, attachment_ids: &[Uuid], message_id: Uuid) -> Result<(), Error> {
+        for attachment_id in attachment_ids {
+            if let Some(mut attachment) = self.get(*attachment_id).await? {
+                attachment.message_id = Some(message_id);
+
+                self.put(&attachment).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn put(&self, attachment: &Attachment) -> Result<(), Error> {
+        self.store
+            .put(
+                attachment.attachment_id.to_string(),
+                serde_json::to_vec(attachment)?.into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get(&self, attachment_id: Uuid) -> Result<Option<Attachment>, Error> {
+        match self.store.get(attachment_id.to_string()).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn urls(&self, attachment: &Attachment) -> Result<AttachmentUrls, Error> {
+        let ttl = Duration::from_secs(self.settings.url_ttl);
+
+        let thumbnail_url = match attachment.thumbnail {
+            true => Some(
+                self.storage
+                    .url(&key(attachment.attachment_id, Variant::Thumbnail), ttl)
+                    .await?,
+            ),
+            false => None,
+        };
+
+        Ok(AttachmentUrls {
+            attachment_id: attachment.attachment_id.into(),
+            name: attachment.name.clone(),
+            mime_type: attachment.mime_type.clone(),
+            size: attachment.size,
+            width: attachment.width,
+            height: attachment.height,
+            url: self
+                .storage
+                .url(&key(attachment.attachment_id, Variant::File), ttl)
+                .await?,
+            thumbnail_url,
+        })
+    }
+
+    // Unknown ids are skipped, messages keep rendering if an attachment expired
+    pub async fn hydrate(
+        &self,
+        attachment_ids: &[String],
+    ) -> Result<HashMap<String, AttachmentUrls>, Error> {
+        let mut attachments = HashMap::new();
+
+        for attachment_id in attachment_ids {
+            if attachments.contains_key(attachment_id) {
+                continue;
+            }
+
+            let Ok(id) = Uuid::parse_str(attachment_id) else {
+                continue;
+            };
+
+            if let Some(attachment) = self.get(id).await? {
+                attachments.insert(attachment_id.clone(), self.urls(&attachment).await?);
+            }
+        }
+
+        Ok(attachments)
+    }
+}
+
+pub fn key(attachment_id: Uuid, variant: Variant) -> String {
+    format!("{}/{}", attachment_id, variant.as_str())
+}
+
+async fn thumbnail(data: Vec<u8>, size: u32) -> Result<(u32, u32, Vec<u8>), Error> {
+    tokio::task::spawn_blocking(move || -> Result<(u32, u32, Vec<u8>), Error> {
+        let image = image::load_from_memory(&data)?;
+        let (width, height) = image.dimensions();
+
+        let mut thumbnail = vec![];
+        image
+            .thumbnail(size, size)
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(
+                &mut Cursor::new(&mut thumbnail),
+                80,
+            ))?;
+
+        Ok((width, height, thumbnail))
+    })
+    .await?
+}
@@ -0,0 +1,42 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use flux_lib::error::Error;
+
+use super::settings::StorageSettings;
+
+mod local;
+mod s3;
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, content_type: &str, data: Vec<u8>) -> Result<(), Error>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error>;
+
+    async fn url(&self, key: &str, ttl: Duration) -> Result<String, Error>;
+
+    // Only URLs served by the gateway itself carry a signature to check
+    fn verify(&self, _key: &str, _expires: u64, _signature: &str) -> bool {
+        false
+    }
+}
+
+pub async fn new(settings: &StorageSettings) -> Result<Arc<dyn Storage>, Error> {
+    Ok(match settings {
+        StorageSettings::Local {
+            path,
+            base_url,
+            secret,
+        } => Arc::new(local::LocalStorage::new(path, base_url, secret)?),
+        StorageSettings::S3 {
+            endpoint,
+            region,
+            bucket,
+            access_key,
+            secret_key,
+        } => Arc::new(s3::S3Storage::new(
+            endpoint, region, bucket, access_key, secret_key,
+        )),
+    })
+}
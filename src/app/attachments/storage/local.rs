@@ -0,0 +1,85 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use flux_lib::error::Error;
+use hmac::{Hmac, Mac as _};
+use sha2::Sha256;
+use tokio::fs;
+
+use super::Storage;
+
+// Files are kept on disk and served by the gateway behind HMAC-signed URLs
+pub struct LocalStorage {
+    path: PathBuf,
+    base_url: String,
+    secret: String,
+}
+
+impl LocalStorage {
+    // An empty secret would let anyone sign download URLs
+    pub fn new(path: &str, base_url: &str, secret: &str) -> Result<Self, Error> {
+        if secret.is_empty() {
+            return Err(Error::msg("attachments: local storage secret is not set"));
+        }
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            secret: secret.to_string(),
+        })
+    }
+
+    fn mac(&self, key: &str, expires: u64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+
+        mac.update(format!("{}:{}", key, expires).as_bytes());
+
+        mac
+    }
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, _content_type: &str, data: Vec<u8>) -> Result<(), Error> {
+        let path = self.path.join(key);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+
+        fs::write(path, data).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(self.path.join(key)).await?)
+    }
+
+    async fn url(&self, key: &str, ttl: Duration) -> Result<String, Error> {
+        let expires = (SystemTime::now().duration_since(UNIX_EPOCH)? + ttl).as_secs();
+        let signature = hex::encode(self.mac(key, expires).finalize().into_bytes());
+
+        Ok(format!(
+            "{}/{}?expires={}&signature={}",
+            self.base_url, key, expires, signature
+        ))
+    }
+
+    fn verify(&self, key: &str, expires: u64, signature: &str) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        expires >= now && self.mac(key, expires).verify_slice(&signature).is_ok()
+    }
+}
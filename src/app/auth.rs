@@ -1,34 +1,93 @@
 use axum::{
-    extract::State,
-    routing::{get, post},
-    Json, Router,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{delete, get, patch, post},
+    Extension, Json, Router,
 };
+use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use uuid::Uuid;
 
 use crate::app::locale::AppLocale;
 
-use super::{error::AppError, state::AppState, user::AppUser};
+use self::jobs::{JobKind, JobStatus};
 
+use super::{
+    error::{AppError, FieldError},
+    state::AppState,
+    user::{AppUser, Scope},
+    validation,
+};
+
+mod account;
+pub mod cookie;
+pub mod denylist;
+pub mod jobs;
+pub mod keys;
+mod messaging;
+pub mod sessions;
 pub mod settings;
+pub mod tokens;
+mod webauthn;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
         .route("/join", post(join))
         .route("/complete", post(complete))
-        .route("/me", get(me))
+        .route("/me", get(me).layer(Extension(Scope::ProfileRead)))
+        .route(
+            "/me",
+            patch(update_me).layer(Extension(Scope::ProfileWrite)),
+        )
+        // Account export and deletion are not available to API tokens
+        .route("/me", delete(delete_me))
+        .route("/me/export", post(export_me))
+        .route("/me/jobs/{job_id}", get(get_job))
+        .route("/me/jobs/{job_id}/archive", get(get_job_archive))
+        // Deletion status stays readable with its token once the user is revoked
+        .route("/jobs/{job_id}", get(get_job_status))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/sessions", get(get_sessions))
+        .route("/sessions/{session_id}", delete(delete_session))
+        .route("/tokens", post(create_token))
+        .route("/tokens", get(get_tokens))
+        .route("/tokens/{token_id}", delete(delete_token))
+}
+
+pub async fn keys(state: &AppState) -> Result<(), AppError> {
+    tokio::spawn(keys::refresh(
+        state.keys.clone(),
+        state.settings.auth.jwks.interval,
+    ));
+
+    Ok(())
+}
+
+pub async fn messaging(state: &AppState) -> Result<(), AppError> {
+    tokio::spawn(messaging::denylist(state.clone()));
+    tokio::spawn(account::resume(state.clone()));
+
+    Ok(())
 }
 
 async fn login(
     State(AppState {
         auth_service_client,
+        settings,
         ..
     }): State<AppState>,
+    jar: CookieJar,
+    headers: HeaderMap,
     Json(data): Json<Value>,
-) -> Result<Json<login::Response>, AppError> {
+) -> Result<(CookieJar, Json<login::Response>), AppError> {
+    let credential: webauthn::PublicKeyCredential = validation::parse(data)?;
+
     let request = flux_users_api::LoginRequest {
-        request: Some(data.to_string()),
+        request: Some(serde_json::to_string(&credential)?),
     };
     let response = auth_service_client
         .clone()
@@ -36,24 +95,18 @@ async fn login(
         .await?
         .into_inner();
 
-    Ok(Json(response.into()))
+    let (jar, jwt) = cookie::login(jar, &settings.auth.cookie, &headers, response.jwt());
+
+    Ok((jar, Json(login::Response { jwt })))
 }
 
 mod login {
-    use flux_users_api::LoginResponse;
     use serde::Serialize;
 
     #[derive(Serialize)]
     pub struct Response {
-        jwt: String,
-    }
-
-    impl From<LoginResponse> for Response {
-        fn from(res: LoginResponse) -> Self {
-            Self {
-                jwt: res.jwt().into(),
-            }
-        }
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub jwt: Option<String>,
     }
 }
 
@@ -63,7 +116,7 @@ async fn join(
         ..
     }): State<AppState>,
     Json(data): Json<JoinRequest>,
-) -> Result<Json<Value>, AppError> {
+) -> Result<Json<webauthn::ChallengeResponse>, AppError> {
     let request: flux_users_api::JoinRequest = data.into();
     let response = auth_service_client
         .clone()
@@ -90,48 +143,61 @@ impl From<JoinRequest> for flux_users_api::JoinRequest {
 async fn complete(
     State(AppState {
         auth_service_client,
+        settings,
         ..
     }): State<AppState>,
     locale: AppLocale,
-    Json(req): Json<complete::Request>,
-) -> Result<Json<CompleteResponse>, AppError> {
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(data): Json<Value>,
+) -> Result<(CookieJar, Json<CompleteResponse>), AppError> {
+    let req: complete::Request = validation::parse(data)?;
+
     let response = auth_service_client
         .clone()
         .complete(flux_users_api::CompleteRequest {
-            first_name: Some(req.first_name.into()),
-            last_name: Some(req.last_name.into()),
+            first_name: Some(req.first_name),
+            last_name: Some(req.last_name),
             locale: Some(locale.to_string()),
-            credential: Some(req.credential.to_string()),
+            credential: Some(serde_json::to_string(&req.credential)?),
         })
         .await?
         .into_inner();
 
-    Ok(Json(response.into()))
+    let (jar, jwt) = cookie::login(jar, &settings.auth.cookie, &headers, response.jwt());
+
+    Ok((jar, Json(CompleteResponse { jwt })))
 }
 
 mod complete {
     use serde::Deserialize;
-    use serde_json::Value;
+
+    use crate::app::{
+        auth::webauthn::RegisterPublicKeyCredential, error::FieldError, validation::Validate,
+    };
 
     #[derive(Deserialize)]
     pub struct Request {
         pub first_name: String,
         pub last_name: String,
-        pub credential: Value,
+        pub credential: RegisterPublicKeyCredential,
     }
-}
 
-impl Into<CompleteResponse> for flux_users_api::CompleteResponse {
-    fn into(self) -> CompleteResponse {
-        CompleteResponse {
-            jwt: self.jwt().into(),
+    impl Validate for Request {
+        fn validate(&self) -> Vec<FieldError> {
+            self.credential
+                .validate()
+                .into_iter()
+                .map(|err| FieldError::new(format!("credential.{}", err.field), err.message))
+                .collect()
         }
     }
 }
 
 #[derive(Serialize)]
 struct CompleteResponse {
-    pub jwt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt: Option<String>,
 }
 
 async fn me(
@@ -163,23 +229,13 @@ async fn me(
 mod me {
     use serde::Serialize;
 
-    use crate::app::error::AppError;
+    use crate::app::{error::AppError, hydration::User};
 
     #[derive(Serialize)]
     pub struct Response {
         pub user: Option<User>,
     }
 
-    #[derive(Serialize)]
-    pub struct User {
-        pub user_id: String,
-        pub name: String,
-        pub first_name: String,
-        pub last_name: String,
-        pub abbr: String,
-        pub color: String,
-    }
-
     impl TryFrom<flux_users_api::MeResponse> for Response {
         type Error = AppError;
 
@@ -187,15 +243,572 @@ mod me {
             let user = res.user.ok_or(AppError::NoEntity)?;
 
             Ok(Response {
-                user: Some(User {
-                    user_id: user.user_id().into(),
-                    name: user.name().into(),
-                    first_name: user.first_name().into(),
-                    last_name: user.last_name().into(),
-                    abbr: user.abbr().into(),
-                    color: user.color().into(),
-                }),
+                user: Some(User::from(user)),
             })
         }
     }
 }
+
+async fn update_me(
+    State(AppState {
+        auth_service_client,
+        hydration,
+        settings,
+        js,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    Json(data): Json<Value>,
+) -> Result<Json<me::Response>, AppError> {
+    let req: update_me::Request = validation::deserialize(data)?;
+    validation::check(&update_me::Profile {
+        req: &req,
+        settings: &settings.auth.profile,
+    })?;
+
+    let current = auth_service_client
+        .clone()
+        .me(flux_users_api::MeRequest {
+            user_id: Some(user.id.into()),
+        })
+        .await?
+        .into_inner()
+        .user
+        .ok_or(AppError::NoEntity)?;
+
+    let first_name: String = match req.first_name {
+        Some(first_name) => first_name.trim().into(),
+        None => current.first_name().into(),
+    };
+    let last_name: String = match req.last_name {
+        Some(last_name) => last_name.trim().into(),
+        None => current.last_name().into(),
+    };
+
+    let response = auth_service_client
+        .clone()
+        .update_me(flux_users_api::UpdateMeRequest {
+            user_id: Some(user.id.into()),
+            abbr: Some(update_me::abbr(&first_name, &last_name)),
+            first_name: Some(first_name),
+            last_name: Some(last_name),
+            color: Some(req.color.unwrap_or_else(|| current.color().into())),
+        })
+        .await?
+        .into_inner();
+
+    let user = response.user.ok_or(AppError::NoEntity)?;
+
+    messaging::user_updated(&js, &settings, update_me::event_user(&user)).await?;
+    hydration.invalidate(user.user_id()).await;
+
+    Ok(Json(me::Response {
+        user: Some(user.into()),
+    }))
+}
+
+mod update_me {
+    use flux_users_api::update_me_response;
+    use serde::Deserialize;
+
+    use crate::app::{auth::settings::ProfileSettings, error::FieldError, validation::Validate};
+
+    #[derive(Deserialize)]
+    pub struct Request {
+        pub first_name: Option<String>,
+        pub last_name: Option<String>,
+        pub color: Option<String>,
+    }
+
+    // Limits come from the settings, so the request is validated together with them
+    pub struct Profile<'a> {
+        pub req: &'a Request,
+        pub settings: &'a ProfileSettings,
+    }
+
+    impl Validate for Profile<'_> {
+        fn validate(&self) -> Vec<FieldError> {
+            let mut errors = vec![];
+
+            for (field, name) in [
+                ("first_name", &self.req.first_name),
+                ("last_name", &self.req.last_name),
+            ] {
+                let Some(name) = name.as_deref().map(str::trim) else {
+                    continue;
+                };
+
+                if name.is_empty() {
+                    errors.push(FieldError::new(field, "must not be empty"));
+                } else if name.chars().any(char::is_control) {
+                    errors.push(FieldError::new(
+                        field,
+                        "must not contain control characters",
+                    ));
+                } else if name.chars().count() > self.settings.name_max_len {
+                    errors.push(FieldError::new(
+                        field,
+                        format!("must be at most {} characters", self.settings.name_max_len),
+                    ));
+                }
+            }
+
+            if let Some(color) = &self.req.color {
+                if !self.settings.colors.contains(color) {
+                    errors.push(FieldError::new(
+                        "color",
+                        "must be one of the palette colors",
+                    ));
+                }
+            }
+
+            errors
+        }
+    }
+
+    pub fn abbr(first_name: &str, last_name: &str) -> String {
+        [first_name, last_name]
+            .iter()
+            .filter_map(|name| name.chars().next())
+            .flat_map(char::to_uppercase)
+            .collect()
+    }
+
+    pub fn event_user(user: &update_me_response::User) -> flux_notify_api::User {
+        flux_notify_api::User {
+            user_id: user.user_id.clone(),
+            name: user.name.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            abbr: user.abbr.clone(),
+            color: user.color.clone(),
+        }
+    }
+}
+
+async fn export_me(
+    State(state): State<AppState>,
+    user: AppUser,
+) -> Result<(StatusCode, Json<job::Response>), AppError> {
+    let job = state.jobs.create(user.id, JobKind::Export).await?;
+
+    tokio::spawn(account::run(state, job.clone()));
+
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
+
+async fn delete_me(
+    State(state): State<AppState>,
+    user: AppUser,
+) -> Result<(StatusCode, Json<job::Response>), AppError> {
+    let (job, token) = state.jobs.create_deletion(user.id).await?;
+
+    tokio::spawn(account::run(state, job.clone()));
+
+    let mut res = job::Response::from(job);
+    res.token = Some(token);
+
+    Ok((StatusCode::ACCEPTED, Json(res)))
+}
+
+async fn get_job(
+    Path(job_id): Path<Uuid>,
+    State(AppState { jobs, .. }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<job::Response>, AppError> {
+    let job = jobs.get(user.id, job_id).await?.ok_or(AppError::NoEntity)?;
+
+    Ok(Json(job.into()))
+}
+
+async fn get_job_status(
+    Path(job_id): Path<Uuid>,
+    Query(query): Query<job::Query>,
+    State(AppState { jobs, .. }): State<AppState>,
+) -> Result<Json<job::Response>, AppError> {
+    let job = jobs
+        .get_by_token(job_id, &query.token)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    Ok(Json(job.into()))
+}
+
+async fn get_job_archive(
+    Path(job_id): Path<Uuid>,
+    State(AppState { jobs, .. }): State<AppState>,
+    user: AppUser,
+) -> Result<impl IntoResponse, AppError> {
+    let job = jobs
+        .get(user.id, job_id)
+        .await?
+        .filter(|job| job.kind == JobKind::Export && job.status == JobStatus::Done)
+        .ok_or(AppError::NoEntity)?;
+
+    let archive = jobs.get_archive(&job).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"flux-export-{}.json\"", job.job_id),
+            ),
+        ],
+        archive,
+    ))
+}
+
+mod job {
+    use serde::{Deserialize, Serialize};
+
+    use crate::app::auth::jobs::{Job, JobKind, JobStatus};
+
+    #[derive(Deserialize)]
+    pub struct Query {
+        pub token: String,
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        job_id: String,
+        kind: JobKind,
+        status: JobStatus,
+        error: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub token: Option<String>,
+    }
+
+    impl From<Job> for Response {
+        fn from(job: Job) -> Self {
+            Self {
+                job_id: job.job_id.into(),
+                kind: job.kind,
+                status: job.status,
+                error: job.error,
+                token: None,
+            }
+        }
+    }
+}
+
+async fn refresh(
+    State(AppState {
+        auth_service_client,
+        denylist,
+        sessions,
+        settings,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    jar: CookieJar,
+    headers: HeaderMap,
+) -> Result<(CookieJar, Json<refresh::Response>), AppError> {
+    let jti = jti(&user)?;
+
+    let response = auth_service_client
+        .clone()
+        .refresh(flux_users_api::RefreshRequest {
+            user_id: Some(user.id.into()),
+        })
+        .await?
+        .into_inner();
+
+    // The refreshed token replaces the old one, which must not stay usable
+    denylist.revoke(user.id, jti).await?;
+    sessions.delete(user.id, jti).await?;
+
+    let (jar, jwt) = cookie::login(jar, &settings.auth.cookie, &headers, response.jwt());
+
+    Ok((jar, Json(refresh::Response { jwt })))
+}
+
+mod refresh {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub jwt: Option<String>,
+    }
+}
+
+async fn logout(
+    State(AppState {
+        denylist,
+        sessions,
+        settings,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    jar: CookieJar,
+) -> Result<(CookieJar, Json<logout::Response>), AppError> {
+    let jti = jti(&user)?;
+
+    denylist.revoke(user.id, jti).await?;
+    sessions.delete(user.id, jti).await?;
+
+    let jar = cookie::logout(jar, &settings.auth.cookie);
+
+    Ok((jar, Json(logout::Response {})))
+}
+
+mod logout {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {}
+}
+
+// A token without `jti` can't be told apart from the user's other tokens, so it can't be
+// revoked. It is refused before anything is changed
+fn jti(user: &AppUser) -> Result<Uuid, AppError> {
+    user.jti.ok_or_else(|| {
+        AppError::Validation(vec![FieldError::new(
+            "authorization",
+            "must be a token with a jti",
+        )])
+    })
+}
+
+async fn get_sessions(
+    State(AppState { sessions, .. }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<get_sessions::Response>, AppError> {
+    let res = (
+        sessions.list(user.id).await?,
+        sessions.list_sockets(user.id).await?,
+        user.jti,
+    );
+
+    Ok(Json(res.into()))
+}
+
+mod get_sessions {
+    use std::cmp::Reverse;
+
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    use crate::app::auth::sessions::{Session, Socket};
+
+    #[derive(Serialize)]
+    pub struct Response {
+        sessions: Vec<ResponseSession>,
+    }
+
+    #[derive(Serialize)]
+    struct ResponseSession {
+        session_id: String,
+        user_agent: Option<String>,
+        ip: Option<String>,
+        device_id: Option<String>,
+        last_seen_at: u64,
+        current: bool,
+        sockets: Vec<ResponseSocket>,
+    }
+
+    #[derive(Serialize)]
+    struct ResponseSocket {
+        socket_id: String,
+        user_agent: Option<String>,
+        ip: Option<String>,
+        connected_at: u64,
+    }
+
+    impl From<(Vec<Session>, Vec<Socket>, Option<Uuid>)> for Response {
+        fn from((sessions, sockets, jti): (Vec<Session>, Vec<Socket>, Option<Uuid>)) -> Self {
+            let mut sessions: Vec<ResponseSession> = sessions
+                .into_iter()
+                .map(|session| ResponseSession {
+                    session_id: session.session_id.into(),
+                    user_agent: session.client.user_agent,
+                    ip: session.client.ip,
+                    device_id: session.device_id,
+                    last_seen_at: session.last_seen_at,
+                    current: Some(session.session_id) == jti,
+                    sockets: sockets
+                        .iter()
+                        .filter(|socket| socket.session_id == session.session_id)
+                        .map(|socket| ResponseSocket {
+                            socket_id: socket.socket_id.into(),
+                            user_agent: socket.client.user_agent.clone(),
+                            ip: socket.client.ip.clone(),
+                            connected_at: socket.connected_at,
+                        })
+                        .collect(),
+                })
+                .collect();
+
+            sessions.sort_by_key(|session| Reverse(session.last_seen_at));
+
+            Self { sessions }
+        }
+    }
+}
+
+async fn delete_session(
+    Path(session_id): Path<Uuid>,
+    State(AppState {
+        sessions,
+        denylist,
+        push_service_client,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<delete_session::Response>, AppError> {
+    let session = sessions
+        .get(user.id, session_id)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    // Sockets of the session are closed by the denylist watcher
+    denylist.revoke(user.id, session.session_id).await?;
+
+    if let Some(device_id) = session.device_id {
+        push_service_client
+            .clone()
+            .delete_web_push(flux_notify_api::DeleteWebPushRequest {
+                user_id: Some(user.id.into()),
+                device_id: Some(device_id),
+            })
+            .await?;
+    }
+
+    sessions.delete(user.id, session_id).await?;
+
+    Ok(Json(delete_session::Response {}))
+}
+
+mod delete_session {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {}
+}
+
+async fn create_token(
+    State(AppState { tokens, .. }): State<AppState>,
+    user: AppUser,
+    Json(data): Json<Value>,
+) -> Result<Json<create_token::Response>, AppError> {
+    let req: create_token::Request = validation::parse(data)?;
+
+    let res = tokens.create(user.id, req.name, req.scopes).await?;
+
+    Ok(Json(res.into()))
+}
+
+mod create_token {
+    use serde::{Deserialize, Serialize};
+
+    use crate::app::{
+        auth::tokens::ApiToken, error::FieldError, user::Scope, validation::Validate,
+    };
+
+    #[derive(Deserialize)]
+    pub struct Request {
+        pub name: String,
+        pub scopes: Vec<Scope>,
+    }
+
+    // A token without scopes can't pass any route, so it is refused up front
+    impl Validate for Request {
+        fn validate(&self) -> Vec<FieldError> {
+            let mut errors = vec![];
+
+            if self.name.trim().is_empty() {
+                errors.push(FieldError::new("name", "must not be empty"));
+            }
+
+            if self.scopes.is_empty() {
+                errors.push(FieldError::new("scopes", "must not be empty"));
+            }
+
+            errors
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        token_id: String,
+        token: String,
+        name: String,
+        scopes: Vec<Scope>,
+    }
+
+    impl From<(ApiToken, String)> for Response {
+        fn from((api_token, token): (ApiToken, String)) -> Self {
+            Self {
+                token_id: api_token.token_id.into(),
+                token,
+                name: api_token.name,
+                scopes: api_token.scopes,
+            }
+        }
+    }
+}
+
+async fn get_tokens(
+    State(AppState { tokens, .. }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<get_tokens::Response>, AppError> {
+    let res = tokens.list(user.id).await?;
+
+    Ok(Json(res.into()))
+}
+
+mod get_tokens {
+    use serde::Serialize;
+
+    use crate::app::{auth::tokens::ApiToken, user::Scope};
+
+    #[derive(Serialize)]
+    pub struct Response {
+        tokens: Vec<Token>,
+    }
+
+    #[derive(Serialize)]
+    struct Token {
+        token_id: String,
+        name: String,
+        scopes: Vec<Scope>,
+    }
+
+    impl From<Vec<ApiToken>> for Response {
+        fn from(api_tokens: Vec<ApiToken>) -> Self {
+            Self {
+                tokens: api_tokens
+                    .into_iter()
+                    .map(|api_token| Token {
+                        token_id: api_token.token_id.into(),
+                        name: api_token.name,
+                        scopes: api_token.scopes,
+                    })
+                    .collect(),
+            }
+        }
+    }
+}
+
+async fn delete_token(
+    Path(token_id): Path<Uuid>,
+    State(AppState { tokens, .. }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<delete_token::Response>, AppError> {
+    tokens
+        .get(user.id, token_id)
+        .await?
+        .ok_or(AppError::NoEntity)?;
+
+    tokens.delete(user.id, token_id).await?;
+
+    Ok(Json(delete_token::Response {}))
+}
+
+mod delete_token {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {}
+}
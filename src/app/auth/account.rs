@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use flux_lib::error::Error;
+use flux_messages_api::{
+    DeleteUserMessagesRequest, GetStreamsRequest, GetUserMessagesRequest, GetUserStreamsRequest,
+};
+use flux_notify_api::{DeleteWebPushesRequest, GetWebPushesRequest};
+use flux_users_api::{DeleteMeRequest, MeRequest};
+use tokio::time;
+use tracing::error;
+
+use crate::app::state::AppState;
+
+use super::jobs::{Job, JobKind, JobStatus};
+
+pub async fn run(state: AppState, mut job: Job) {
+    job.status = JobStatus::Running;
+
+    if let Err(err) = state.jobs.put(&mut job).await {
+        error!("{}", err);
+    }
+
+    let res = match job.kind {
+        JobKind::Export => export(&state, &job).await,
+        JobKind::Delete => delete(&state, &mut job).await,
+    };
+
+    (job.status, job.error) = match res {
+        Ok(()) => (JobStatus::Done, None),
+        Err(err) => {
+            error!("{}", err);
+
+            (JobStatus::Failed, Some(err.to_string()))
+        }
+    };
+
+    if let Err(err) = state.jobs.put(&mut job).await {
+        error!("{}", err);
+    }
+
+    if job.kind == JobKind::Delete && job.status == JobStatus::Done {
+        if let Err(err) = state.jobs.complete_deletion(&job).await {
+            error!("{}", err);
+        }
+    }
+}
+
+// The user can't retry a deletion once revoked, so failed or orphaned ones are picked up here
+pub async fn resume(state: AppState) {
+    let mut interval = time::interval(Duration::from_secs(state.settings.auth.jobs.lease.get()));
+
+    loop {
+        interval.tick().await;
+
+        let deletions = match state.jobs.deletions().await {
+            Ok(deletions) => deletions,
+            Err(err) => {
+                error!("{}", err);
+                continue;
+            }
+        };
+
+        for (user_id, job_id) in deletions {
+            match state.jobs.claim(user_id, job_id).await {
+                Ok(Some(job)) => run(state.clone(), job).await,
+                Ok(None) => {}
+                Err(err) => error!("{}", err),
+            }
+        }
+    }
+}
+
+async fn export(state: &AppState, job: &Job) -> Result<(), Error> {
+    let AppState {
+        auth_service_client,
+        streams_service_client,
+        messages_service_client,
+        push_service_client,
+        jobs,
+        settings,
+        ..
+    } = state;
+
+    let user_id: String = job.user_id.into();
+
+    let me_response = auth_service_client
+        .clone()
+        .me(MeRequest {
+            user_id: Some(user_id.clone()),
+        })
+        .await?
+        .into_inner();
+
+    let mut streams = vec![];
+    let mut before_stream_id = None;
+
+    loop {
+        let get_user_streams_response = streams_service_client
+            .clone()
+            .get_user_streams(GetUserStreamsRequest {
+                user_id: Some(user_id.clone()),
+                limit: Some(settings.auth.export.limit),
+                before_stream_id,
+                after_stream_id: None,
+            })
+            .await?
+            .into_inner();
+
+        if get_user_streams_response.stream_ids.is_empty() {
+            break;
+        }
+
+        let get_streams_response = streams_service_client
+            .clone()
+            .get_streams(GetStreamsRequest {
+                stream_ids: get_user_streams_response.stream_ids,
+            })
+            .await?
+            .into_inner();
+
+        streams.extend(get_streams_response.streams);
+
+        match get_user_streams_response.next_stream_id {
+            Some(next_stream_id) => before_stream_id = Some(next_stream_id),
+            None => break,
+        }
+    }
+
+    let mut messages = vec![];
+    let mut cursor_message_id = None;
+
+    loop {
+        let get_user_messages_response = messages_service_client
+            .clone()
+            .get_user_messages(GetUserMessagesRequest {
+                user_id: Some(user_id.clone()),
+                cursor_message_id,
+                limit: Some(settings.auth.export.limit),
+            })
+            .await?
+            .into_inner();
+
+        let done = get_user_messages_response.messages.is_empty();
+        messages.extend(get_user_messages_response.messages);
+        cursor_message_id = get_user_messages_response.cursor_message_id;
+
+        if done || cursor_message_id.is_none() {
+            break;
+        }
+    }
+
+    let get_web_pushes_response = push_service_client
+        .clone()
+        .get_web_pushes(GetWebPushesRequest {
+            user_id: Some(user_id),
+        })
+        .await?
+        .into_inner();
+
+    let archive = archive::Archive {
+        user: me_response.user.map(Into::into),
+        streams: streams.into_iter().map(Into::into).collect(),
+        messages: messages.into_iter().map(Into::into).collect(),
+        device_ids: get_web_pushes_response.device_ids,
+    };
+
+    jobs.put_archive(job, &serde_json::to_vec_pretty(&archive)?)
+        .await?;
+
+    Ok(())
+}
+
+// Users are removed last, so a failed job can be retried while the account still exists.
+// Every step can run again, the job is written after each one to keep its lease
+async fn delete(state: &AppState, job: &mut Job) -> Result<(), Error> {
+    let AppState {
+        auth_service_client,
+        messages_service_client,
+        push_service_client,
+        tokens,
+        denylist,
+        sessions,
+        jobs,
+        ..
+    } = state;
+
+    let user_id: String = job.user_id.into();
+
+    messages_service_client
+        .clone()
+        .delete_user_messages(DeleteUserMessagesRequest {
+            user_id: Some(user_id.clone()),
+        })
+        .await?;
+    jobs.put(job).await?;
+
+    push_service_client
+        .clone()
+        .delete_web_pushes(DeleteWebPushesRequest {
+            user_id: Some(user_id.clone()),
+        })
+        .await?;
+    jobs.put(job).await?;
+
+    for api_token in tokens.list(job.user_id).await? {
+        tokens.delete(job.user_id, api_token.token_id).await?;
+    }
+
+    // Revoked before the user is gone, so a failure here can't leave live tokens behind.
+    // Open sockets are closed by the denylist watcher
+    denylist.revoke_user(job.user_id).await?;
+    sessions.purge(job.user_id).await?;
+    jobs.put(job).await?;
+
+    auth_service_client
+        .clone()
+        .delete_me(DeleteMeRequest {
+            user_id: Some(user_id),
+        })
+        .await?;
+
+    Ok(())
+}
+
+mod archive {
+    use flux_messages_api::{get_message_response, get_streams_response};
+    use serde::Serialize;
+
+    use crate::app::hydration::User;
+
+    #[derive(Serialize)]
+    pub struct Archive {
+        pub user: Option<User>,
+        pub streams: Vec<Stream>,
+        pub messages: Vec<Message>,
+        pub device_ids: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Stream {
+        stream_id: String,
+        message_id: String,
+        text: Option<String>,
+        user_ids: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Message {
+        message_id: String,
+        stream_id: Option<String>,
+        text: String,
+        code: String,
+        order: i64,
+    }
+
+    impl From<get_streams_response::Stream> for Stream {
+        fn from(stream: get_streams_response::Stream) -> Self {
+            Self {
+                stream_id: stream.stream_id().into(),
+                message_id: stream.message_id().into(),
+                text: stream.text,
+                user_ids: stream.user_ids,
+            }
+        }
+    }
+
+    impl From<get_message_response::Message> for Message {
+        fn from(message: get_message_response::Message) -> Self {
+            Self {
+                message_id: message.message_id().into(),
+                text: message.text().into(),
+                code: message.code().into(),
+                order: message.order(),
+                stream_id: message.stream_id,
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use axum::http::{request::Parts, HeaderMap, Method};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use subtle::ConstantTimeEq as _;
+use time::Duration;
+use uuid::Uuid;
+
+use crate::app::error::AppError;
+
+use super::settings::CookieSettings;
+
+// Browser clients opt in per request with the mode header, everyone else keeps the JWT in the body.
+// Returns the JWT for the response body, which is left out when the cookie carries it
+pub fn login(
+    jar: CookieJar,
+    settings: &CookieSettings,
+    headers: &HeaderMap,
+    jwt: &str,
+) -> (CookieJar, Option<String>) {
+    let requested = headers
+        .get(&settings.mode_header)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"cookie"));
+
+    if !settings.enabled || !requested {
+        return (jar, Some(jwt.to_string()));
+    }
+
+    let session = Cookie::build((settings.name.clone(), jwt.to_string()))
+        .path("/")
+        .http_only(true)
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(settings.max_age));
+
+    // Readable by scripts on purpose, it is echoed back in the CSRF header
+    let csrf = Cookie::build((settings.csrf_name.clone(), Uuid::new_v4().to_string()))
+        .path("/")
+        .secure(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(settings.max_age));
+
+    (jar.add(session).add(csrf), None)
+}
+
+pub fn logout(jar: CookieJar, settings: &CookieSettings) -> CookieJar {
+    if !settings.enabled {
+        return jar;
+    }
+
+    jar.remove(Cookie::build((settings.name.clone(), "")).path("/"))
+        .remove(Cookie::build((settings.csrf_name.clone(), "")).path("/"))
+}
+
+pub fn token(parts: &Parts, settings: &CookieSettings) -> Result<Option<String>, AppError> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+
+    let jar = CookieJar::from_headers(&parts.headers);
+
+    let Some(session) = jar.get(&settings.name) else {
+        return Ok(None);
+    };
+
+    if !matches!(parts.method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        let header = parts
+            .headers
+            .get(&settings.csrf_header)
+            .and_then(|v| v.to_str().ok());
+
+        match (header, jar.get(&settings.csrf_name)) {
+            (Some(header), Some(csrf))
+                if bool::from(header.as_bytes().ct_eq(csrf.value().as_bytes())) => {}
+            _ => return Err(AppError::Forbidden),
+        }
+    }
+
+    Ok(Some(session.value().into()))
+}
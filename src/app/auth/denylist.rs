@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use async_nats::jetstream::kv;
+use flux_lib::error::Error;
+use uuid::Uuid;
+
+use crate::app::AppJS;
+
+use super::settings::DenylistSettings;
+
+// Revoked users are keyed apart from token ids, every token of theirs is rejected
+pub const USERS_PREFIX: &str = "users.";
+
+#[derive(Clone)]
+pub struct AuthDenylist {
+    store: kv::Store,
+}
+
+impl AuthDenylist {
+    pub async fn new(js: &AppJS, settings: &DenylistSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                max_age: Duration::from_secs(settings.max_age),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self { store })
+    }
+
+    // Tokens are revoked by `jti`, see `auth::jti` for tokens without it
+    pub async fn revoke(&self, user_id: Uuid, jti: Uuid) -> Result<(), Error> {
+        self.store
+            .put(jti.to_string(), user_id.to_string().into())
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke_user(&self, user_id: Uuid) -> Result<(), Error> {
+        self.store
+            .put(
+                format!("{}{}", USERS_PREFIX, user_id),
+                user_id.to_string().into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_revoked(&self, jti: Uuid) -> Result<bool, Error> {
+        Ok(self.store.get(jti.to_string()).await?.is_some())
+    }
+
+    pub async fn is_user_revoked(&self, user_id: Uuid) -> Result<bool, Error> {
+        Ok(self
+            .store
+            .get(format!("{}{}", USERS_PREFIX, user_id))
+            .await?
+            .is_some())
+    }
+
+    // A watch that broke resumes after the last revision it saw
+    pub async fn watch(&self, revision: u64) -> Result<kv::Watch, Error> {
+        Ok(match revision {
+            0 => self.store.watch_all().await?,
+            revision => self.store.watch_all_from_revision(revision + 1).await?,
+        })
+    }
+}
@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use async_nats::jetstream::{kv, object_store};
+use flux_lib::error::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use subtle::ConstantTimeEq as _;
+use tokio::io::AsyncReadExt as _;
+use uuid::Uuid;
+
+use crate::app::AppJS;
+
+use super::{sessions, settings::JobsSettings};
+
+// Deletions are also listed under this prefix until they are done, see `deletions`
+const DELETIONS_PREFIX: &str = "deletions";
+
+#[derive(Clone)]
+pub struct AuthJobs {
+    store: kv::Store,
+    archives: object_store::ObjectStore,
+    lease: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Job {
+    pub job_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: JobKind,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    // Refreshed by every write of a running job, a stale one belongs to a dead replica
+    #[serde(default)]
+    pub updated_at: u64,
+    // Hash of the secret that reads the status once the user can no longer authenticate
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Export,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl AuthJobs {
+    pub async fn new(js: &AppJS, settings: &JobsSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                max_age: Duration::from_secs(settings.max_age),
+                ..Default::default()
+            })
+            .await?;
+
+        let archives = js
+            .create_object_store(object_store::Config {
+                bucket: settings.archives.clone(),
+                max_age: Duration::from_secs(settings.max_age),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            store,
+            archives,
+            lease: settings.lease.get(),
+        })
+    }
+
+    pub async fn create(&self, user_id: Uuid, kind: JobKind) -> Result<Job, Error> {
+        let mut job = Job {
+            job_id: Uuid::now_v7(),
+            user_id,
+            kind,
+            status: JobStatus::Pending,
+            error: None,
+            updated_at: 0,
+            token: None,
+        };
+
+        self.put(&mut job).await?;
+
+        Ok(job)
+    }
+
+    // The user is revoked while the job runs, so its status is read with the returned token
+    pub async fn create_deletion(&self, user_id: Uuid) -> Result<(Job, String), Error> {
+        let secret = Uuid::new_v4().simple().to_string();
+
+        let mut job = Job {
+            job_id: Uuid::now_v7(),
+            user_id,
+            kind: JobKind::Delete,
+            status: JobStatus::Running,
+            error: None,
+            updated_at: 0,
+            token: Some(hash(&secret)),
+        };
+
+        self.put(&mut job).await?;
+        self.store
+            .put(deletion_key(user_id, job.job_id), Default::default())
+            .await?;
+
+        Ok((job, format!("{}.{}", user_id, secret)))
+    }
+
+    // A job that stopped being written to is reported as failed, its replica is gone
+    pub async fn get(&self, user_id: Uuid, job_id: Uuid) -> Result<Option<Job>, Error> {
+        let Some(mut job) = self.load(user_id, job_id).await? else {
+            return Ok(None);
+        };
+
+        if matches!(job.status, JobStatus::Pending | JobStatus::Running) && self.is_stale(&job) {
+            job.status = JobStatus::Failed;
+            job.error = Some("job was interrupted".into());
+        }
+
+        Ok(Some(job))
+    }
+
+    pub async fn get_by_token(&self, job_id: Uuid, token: &str) -> Result<Option<Job>, Error> {
+        let Some((user_id, secret)) = token.split_once('.') else {
+            return Ok(None);
+        };
+
+        let Ok(user_id) = Uuid::parse_str(user_id) else {
+            return Ok(None);
+        };
+
+        Ok(self.get(user_id, job_id).await?.filter(|job| {
+            job.token
+                .as_ref()
+                .is_some_and(|token| bool::from(token.as_bytes().ct_eq(hash(secret).as_bytes())))
+        }))
+    }
+
+    // Deletions that are not done yet, they are resumed by `account::resume`
+    pub async fn deletions(&self) -> Result<Vec<(Uuid, Uuid)>, Error> {
+        Ok(sessions::keys(&self.store, DELETIONS_PREFIX)
+            .await?
+            .iter()
+            .filter_map(|key| {
+                let mut ids = key.split('.').skip(1).map(Uuid::parse_str);
+
+                Some((ids.next()?.ok()?, ids.next()?.ok()?))
+            })
+            .collect())
+    }
+
+    // Runs are exclusive, the status is switched with the revision it was read at
+    pub async fn claim(&self, user_id: Uuid, job_id: Uuid) -> Result<Option<Job>, Error> {
+        let Some(entry) = self.store.entry(key(user_id, job_id)).await? else {
+            return Ok(None);
+        };
+
+        let mut job: Job = serde_json::from_slice(&entry.value)?;
+
+        if job.status == JobStatus::Done
+            || (matches!(job.status, JobStatus::Pending | JobStatus::Running)
+                && !self.is_stale(&job))
+        {
+            return Ok(None);
+        }
+
+        job.status = JobStatus::Running;
+        job.updated_at = sessions::now();
+
+        Ok(
+            match self
+                .store
+                .update(
+                    key(user_id, job_id),
+                    serde_json::to_vec(&job)?.into(),
+                    entry.revision,
+                )
+                .await
+            {
+                Ok(_) => Some(job),
+                Err(_) => None,
+            },
+        )
+    }
+
+    pub async fn complete_deletion(&self, job: &Job) -> Result<(), Error> {
+        self.store
+            .purge(deletion_key(job.user_id, job.job_id))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, user_id: Uuid, job_id: Uuid) -> Result<Option<Job>, Error> {
+        match self.store.get(key(user_id, job_id)).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn is_stale(&self, job: &Job) -> bool {
+        job.updated_at + self.lease < sessions::now()
+    }
+
+    pub async fn put(&self, job: &mut Job) -> Result<(), Error> {
+        job.updated_at = sessions::now();
+
+        self.store
+            .put(
+                key(job.user_id, job.job_id),
+                serde_json::to_vec(job)?.into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn put_archive(&self, job: &Job, archive: &[u8]) -> Result<(), Error> {
+        self.archives
+            .put(key(job.user_id, job.job_id).as_str(), &mut &archive[..])
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_archive(&self, job: &Job) -> Result<Vec<u8>, Error> {
+        let mut object = self.archives.get(key(job.user_id, job.job_id)).await?;
+        let mut archive = vec![];
+
+        object.read_to_end(&mut archive).await?;
+
+        Ok(archive)
+    }
+}
+
+fn key(user_id: Uuid, job_id: Uuid) -> String {
+    format!("{}.{}", user_id, job_id)
+}
+
+fn deletion_key(user_id: Uuid, job_id: Uuid) -> String {
+    format!("{}.{}.{}", DELETIONS_PREFIX, user_id, job_id)
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
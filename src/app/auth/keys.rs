@@ -0,0 +1,206 @@
+use std::{collections::HashMap, num::NonZeroU64, str::FromStr as _, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use flux_lib::error::Error;
+use jsonwebtoken::{
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, PublicKeyUse},
+    Algorithm, DecodingKey,
+};
+use tokio::{fs, sync::RwLock, time};
+use tracing::{error, info, warn};
+
+use super::settings::AuthSettings;
+
+#[derive(Clone)]
+pub struct AuthKeys {
+    endpoint: String,
+    algorithms: Vec<Algorithm>,
+    jwks: Arc<RwLock<Jwks>>,
+}
+
+#[derive(Clone)]
+pub struct AuthKey {
+    pub algorithm: Algorithm,
+    pub decoding_key: DecodingKey,
+}
+
+#[derive(Default)]
+struct Jwks {
+    raw: String,
+    keys: HashMap<String, AuthKey>,
+}
+
+impl AuthKeys {
+    pub async fn new(settings: &AuthSettings) -> Result<Self, Error> {
+        let keys = Self {
+            endpoint: settings.jwks.endpoint.clone(),
+            algorithms: settings.algorithms.clone(),
+            jwks: Arc::default(),
+        };
+
+        keys.load().await?;
+
+        Ok(keys)
+    }
+
+    // Tokens without `kid` are only accepted while the set holds a single key
+    pub async fn get(&self, kid: Option<&str>) -> Option<AuthKey> {
+        let jwks = self.jwks.read().await;
+
+        match kid {
+            Some(kid) => jwks.keys.get(kid).cloned(),
+            None if jwks.keys.len() == 1 => jwks.keys.values().next().cloned(),
+            None => None,
+        }
+    }
+
+    async fn load(&self) -> Result<(), Error> {
+        let raw = fetch(&self.endpoint).await?;
+
+        if self.jwks.read().await.raw == raw {
+            return Ok(());
+        }
+
+        let keys = if raw.trim_start().starts_with("-----BEGIN") {
+            vec![(String::new(), AuthKey::from_pem(raw.as_bytes())?)]
+        } else {
+            let jwk_set: JwkSet = serde_json::from_str(&raw)?;
+
+            // Keys that can't verify tokens are skipped so one of them doesn't fail the whole set
+            jwk_set
+                .keys
+                .iter()
+                .filter_map(|jwk| {
+                    let kid = jwk.common.key_id.clone().unwrap_or_default();
+
+                    match AuthKey::try_from(jwk) {
+                        Ok(key) => Some((kid, key)),
+                        Err(err) => {
+                            warn!("auth: skip key {}: {}", kid, err);
+
+                            None
+                        }
+                    }
+                })
+                .collect()
+        };
+
+        let keys = keys
+            .into_iter()
+            .filter(|(kid, key)| {
+                let allowed = self.algorithms.contains(&key.algorithm);
+
+                if !allowed {
+                    warn!("auth: skip key {} with {:?}", kid, key.algorithm);
+                }
+
+                allowed
+            })
+            .collect::<HashMap<String, AuthKey>>();
+
+        info!("auth: loaded {} keys from {}", keys.len(), self.endpoint);
+
+        *self.jwks.write().await = Jwks { raw, keys };
+
+        Ok(())
+    }
+}
+
+impl AuthKey {
+    // Public keys share the SPKI envelope, so the key type is probed in turn
+    fn from_pem(pem: &[u8]) -> Result<Self, Error> {
+        let (algorithm, decoding_key) = if let Ok(key) = DecodingKey::from_ed_pem(pem) {
+            (Algorithm::EdDSA, key)
+        } else if let Ok(key) = DecodingKey::from_ec_pem(pem) {
+            (ec_algorithm(pem)?, key)
+        } else {
+            (Algorithm::RS256, DecodingKey::from_rsa_pem(pem)?)
+        };
+
+        Ok(Self {
+            algorithm,
+            decoding_key,
+        })
+    }
+}
+
+impl TryFrom<&Jwk> for AuthKey {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self, Self::Error> {
+        if jwk.common.public_key_use == Some(PublicKeyUse::Encryption) {
+            return Err(Error::msg("auth: encryption key"));
+        }
+
+        let algorithm = match (jwk.common.key_algorithm, &jwk.algorithm) {
+            (Some(key_algorithm), _) => Algorithm::from_str(&key_algorithm.to_string())?,
+            (None, AlgorithmParameters::RSA(_)) => Algorithm::RS256,
+            (None, AlgorithmParameters::EllipticCurve(params)) => match params.curve {
+                EllipticCurve::P256 => Algorithm::ES256,
+                EllipticCurve::P384 => Algorithm::ES384,
+                _ => return Err(Error::msg("auth: unsupported curve")),
+            },
+            (None, AlgorithmParameters::OctetKeyPair(params))
+                if params.curve == EllipticCurve::Ed25519 =>
+            {
+                Algorithm::EdDSA
+            }
+            _ => return Err(Error::msg("auth: unsupported key type")),
+        };
+
+        Ok(Self {
+            algorithm,
+            decoding_key: DecodingKey::from_jwk(jwk)?,
+        })
+    }
+}
+
+// DER encoded named curve OIDs of P-256 (1.2.840.10045.3.1.7) and P-384 (1.3.132.0.34)
+const P256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const P384: &[u8] = &[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22];
+
+// The curve is read from the key's parameters, EC PEMs don't name it anywhere else
+fn ec_algorithm(pem: &[u8]) -> Result<Algorithm, Error> {
+    let body: String = String::from_utf8_lossy(pem)
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+
+    let der = STANDARD.decode(body.trim())?;
+    let has = |oid: &[u8]| der.windows(oid.len()).any(|window| window == oid);
+
+    if has(P256) {
+        Ok(Algorithm::ES256)
+    } else if has(P384) {
+        Ok(Algorithm::ES384)
+    } else {
+        Err(Error::msg("auth: unsupported curve"))
+    }
+}
+
+async fn fetch(endpoint: &str) -> Result<String, Error> {
+    if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+        Ok(reqwest::get(endpoint)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?)
+    } else {
+        Ok(fs::read_to_string(endpoint).await?)
+    }
+}
+
+pub async fn refresh(keys: AuthKeys, interval: NonZeroU64) {
+    let mut interval = time::interval(Duration::from_secs(interval.get()));
+
+    // The first tick completes immediately and keys are already loaded on start
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = keys.load().await {
+            error!("{}", err);
+        }
+    }
+}
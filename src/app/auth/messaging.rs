@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use flux_lib::error::Error;
+use flux_notify_api::event::Payload;
+use prost::Message as _;
+use tokio::time;
+use tokio_stream::StreamExt as _;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app::{settings::AppSettings, state::AppState, AppJS};
+
+use super::denylist::USERS_PREFIX;
+
+// Sockets are closed on revocation for as long as the gateway runs, so a broken watch is resumed
+pub async fn denylist(state: AppState) {
+    let mut revision = 0;
+
+    loop {
+        if let Err(err) = watch(&state, &mut revision).await {
+            error!("denylist: {}", err);
+        }
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn watch(state: &AppState, revision: &mut u64) -> Result<(), Error> {
+    let AppState {
+        denylist, notify, ..
+    } = state;
+
+    let mut entries = denylist.watch(*revision).await?;
+
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        *revision = entry.revision;
+        let key = entry.key;
+
+        match key.strip_prefix(USERS_PREFIX) {
+            Some(user_id) => {
+                if let Ok(user_id) = Uuid::parse_str(user_id) {
+                    let _ = notify.deleted.send(user_id);
+                }
+            }
+            None => {
+                if let Ok(jti) = Uuid::parse_str(&key) {
+                    let _ = notify.revoked.send(jti);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn user_updated(
+    js: &AppJS,
+    settings: &AppSettings,
+    user: flux_notify_api::User,
+) -> Result<(), Error> {
+    let event = flux_notify_api::Event {
+        payload: Some(Payload::UserUpdated(user)),
+    };
+
+    js.publish(
+        settings.auth.messaging.user_updated.subject.clone(),
+        event.encode_to_vec().into(),
+    )
+    .await?
+    .await?;
+
+    Ok(())
+}
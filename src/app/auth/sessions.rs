@@ -0,0 +1,304 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_nats::jetstream::kv;
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::{header, request::Parts},
+};
+use flux_lib::error::Error;
+use moka::future::Cache;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+use tokio_stream::StreamExt as _;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app::{state::AppState, AppJS};
+
+use super::settings::SessionsSettings;
+
+#[derive(Clone)]
+pub struct AuthSessions {
+    store: kv::Store,
+    sockets: kv::Store,
+    interval: Duration,
+    touched: Cache<Uuid, ()>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub client: Client,
+    pub device_id: Option<String>,
+    pub last_seen_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Socket {
+    pub socket_id: Uuid,
+    pub session_id: Uuid,
+    pub client: Client,
+    pub connected_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Client {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl AuthSessions {
+    pub async fn new(js: &AppJS, settings: &SessionsSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                max_age: Duration::from_secs(settings.max_age),
+                ..Default::default()
+            })
+            .await?;
+
+        // Socket entries are kept alive while connected, so stale ones expire quickly
+        let sockets = js
+            .create_key_value(kv::Config {
+                bucket: settings.sockets.clone(),
+                max_age: Duration::from_secs(settings.interval * 3),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            store,
+            sockets,
+            interval: Duration::from_secs(settings.interval),
+            touched: Cache::builder()
+                .time_to_live(Duration::from_secs(settings.interval))
+                .build(),
+        })
+    }
+
+    // Writes are throttled per session to one every `interval`
+    pub async fn touch(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        client: Client,
+    ) -> Result<(), Error> {
+        // Entries expire after `interval`, only the request that inserts one writes the session
+        if !self
+            .touched
+            .entry(session_id)
+            .or_insert(())
+            .await
+            .is_fresh()
+        {
+            return Ok(());
+        }
+
+        let device_id = self
+            .get(user_id, session_id)
+            .await?
+            .and_then(|session| session.device_id);
+
+        self.put(&Session {
+            session_id,
+            user_id,
+            client,
+            device_id,
+            last_seen_at: now(),
+        })
+        .await
+    }
+
+    pub async fn set_device(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+        device_id: String,
+    ) -> Result<(), Error> {
+        if let Some(mut session) = self.get(user_id, session_id).await? {
+            session.device_id = Some(device_id);
+
+            self.put(&session).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get(&self, user_id: Uuid, session_id: Uuid) -> Result<Option<Session>, Error> {
+        match self.store.get(key(user_id, session_id)).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<Session>, Error> {
+        list(&self.store, user_id).await
+    }
+
+    pub async fn list_sockets(&self, user_id: Uuid) -> Result<Vec<Socket>, Error> {
+        list(&self.sockets, user_id).await
+    }
+
+    pub async fn delete(&self, user_id: Uuid, session_id: Uuid) -> Result<(), Error> {
+        self.store.delete(key(user_id, session_id)).await?;
+        self.touched.invalidate(&session_id).await;
+
+        Ok(())
+    }
+
+    // Removes every session and socket entry of a deleted user
+    pub async fn purge(&self, user_id: Uuid) -> Result<(), Error> {
+        for store in [&self.store, &self.sockets] {
+            for key in keys(store, user_id).await? {
+                store.purge(key).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs for as long as the socket is open, the caller drops it on disconnect
+    pub async fn keep_socket(&self, user_id: Uuid, socket: Socket) {
+        let mut interval = time::interval(self.interval);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(err) = self.put_socket(user_id, &socket).await {
+                error!("{}", err);
+            }
+        }
+    }
+
+    async fn put_socket(&self, user_id: Uuid, socket: &Socket) -> Result<(), Error> {
+        self.sockets
+            .put(
+                key(user_id, socket.socket_id),
+                serde_json::to_vec(socket)?.into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn close_socket(&self, user_id: Uuid, socket_id: Uuid) -> Result<(), Error> {
+        self.sockets.delete(key(user_id, socket_id)).await?;
+
+        Ok(())
+    }
+
+    async fn put(&self, session: &Session) -> Result<(), Error> {
+        self.store
+            .put(
+                key(session.user_id, session.session_id),
+                serde_json::to_vec(session)?.into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Client {
+    pub fn from_parts(parts: &Parts, trusted_proxies: &[IpAddr]) -> Self {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        // X-Forwarded-For is only believed from a trusted proxy, the client is the last
+        // address that was not added by one
+        let forwarded = peer
+            .filter(|peer| trusted_proxies.contains(peer))
+            .and_then(|_| parts.headers.get("x-forwarded-for"))
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.split(',').map(str::trim).rev().find(|ip| {
+                    !ip.parse::<IpAddr>()
+                        .is_ok_and(|ip| trusted_proxies.contains(&ip))
+                })
+            })
+            .map(|v| v.to_string());
+
+        let ip = forwarded.or_else(|| peer.map(|peer| peer.to_string()));
+
+        Self { user_agent, ip }
+    }
+}
+
+impl<S> FromRequestParts<S> for Client
+where
+    AppState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AppState { settings, .. } = AppState::from_ref(state);
+
+        Ok(Self::from_parts(
+            parts,
+            &settings.auth.sessions.trusted_proxies,
+        ))
+    }
+}
+
+pub async fn list<T: serde::de::DeserializeOwned>(
+    store: &kv::Store,
+    user_id: Uuid,
+) -> Result<Vec<T>, Error> {
+    let mut values = vec![];
+
+    // Deleted keys keep their subject until the marker is purged
+    for key in keys(store, user_id).await? {
+        if let Some(value) = store.get(key).await? {
+            values.push(serde_json::from_slice(&value)?);
+        }
+    }
+
+    Ok(values)
+}
+
+// Only the user's subjects of the bucket stream are listed, other users' keys are not scanned
+pub async fn keys(
+    store: &kv::Store,
+    user_id: impl std::fmt::Display,
+) -> Result<Vec<String>, Error> {
+    let mut subjects = store
+        .stream
+        .info_with_subjects(format!("{}{}.>", store.prefix, user_id))
+        .await?;
+    let mut keys = vec![];
+
+    while let Some(subject) = subjects.next().await {
+        let (subject, _) = subject?;
+
+        if let Some(key) = subject.strip_prefix(&store.prefix) {
+            keys.push(key.to_string());
+        }
+    }
+
+    Ok(keys)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn key(user_id: Uuid, id: Uuid) -> String {
+    format!("{}.{}", user_id, id)
+}
@@ -1,6 +1,83 @@
+use std::{net::IpAddr, num::NonZeroU64};
+
+use jsonwebtoken::Algorithm;
 use serde::Deserialize;
 
 #[derive(Deserialize, Clone)]
 pub struct AuthSettings {
-    pub public_key_file: String,
+    pub algorithms: Vec<Algorithm>,
+    pub jwks: JwksSettings,
+    pub denylist: DenylistSettings,
+    pub cookie: CookieSettings,
+    pub tokens: TokensSettings,
+    pub sessions: SessionsSettings,
+    pub jobs: JobsSettings,
+    pub export: ExportSettings,
+    pub profile: ProfileSettings,
+    pub messaging: MessagingSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct JwksSettings {
+    pub endpoint: String,
+    pub interval: NonZeroU64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DenylistSettings {
+    pub bucket: String,
+    pub max_age: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct CookieSettings {
+    pub enabled: bool,
+    pub name: String,
+    pub csrf_name: String,
+    pub csrf_header: String,
+    pub mode_header: String,
+    pub max_age: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TokensSettings {
+    pub bucket: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SessionsSettings {
+    pub bucket: String,
+    pub sockets: String,
+    pub max_age: u64,
+    pub interval: u64,
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct JobsSettings {
+    pub bucket: String,
+    pub archives: String,
+    pub max_age: u64,
+    pub lease: NonZeroU64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ExportSettings {
+    pub limit: i64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ProfileSettings {
+    pub name_max_len: usize,
+    pub colors: Vec<String>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessagingSettings {
+    pub user_updated: MessagingSubjectSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessagingSubjectSettings {
+    pub subject: String,
 }
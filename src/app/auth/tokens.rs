@@ -0,0 +1,120 @@
+use async_nats::jetstream::kv;
+use flux_lib::error::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
+
+use crate::app::{user::Scope, AppJS};
+
+use super::{sessions, settings::TokensSettings};
+
+pub const PREFIX: &str = "flux_";
+
+#[derive(Clone)]
+pub struct AuthTokens {
+    store: kv::Store,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiToken {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    hash: String,
+}
+
+impl AuthTokens {
+    pub async fn new(js: &AppJS, settings: &TokensSettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self { store })
+    }
+
+    // Only the hash of the secret is kept, the token itself is returned once
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        name: String,
+        scopes: Vec<Scope>,
+    ) -> Result<(ApiToken, String), Error> {
+        let token_id = Uuid::now_v7();
+        let secret = Uuid::new_v4().simple().to_string();
+
+        let api_token = ApiToken {
+            token_id,
+            user_id,
+            name,
+            scopes,
+            hash: hash(&secret),
+        };
+
+        self.store
+            .put(
+                key(user_id, token_id),
+                serde_json::to_vec(&api_token)?.into(),
+            )
+            .await?;
+
+        let token = format!(
+            "{}{}_{}_{}",
+            PREFIX,
+            user_id.simple(),
+            token_id.simple(),
+            secret
+        );
+
+        Ok((api_token, token))
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<ApiToken, Error> {
+        let mut parts = token.splitn(3, '_');
+
+        let (Some(user_id), Some(token_id), Some(secret)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(Error::msg("auth: malformed token"));
+        };
+
+        let api_token = self
+            .get(Uuid::parse_str(user_id)?, Uuid::parse_str(token_id)?)
+            .await?
+            .ok_or_else(|| Error::msg("auth: unknown token"))?;
+
+        if api_token.hash != hash(secret) {
+            return Err(Error::msg("auth: unknown token"));
+        }
+
+        Ok(api_token)
+    }
+
+    pub async fn get(&self, user_id: Uuid, token_id: Uuid) -> Result<Option<ApiToken>, Error> {
+        match self.store.get(key(user_id, token_id)).await? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<ApiToken>, Error> {
+        sessions::list(&self.store, user_id).await
+    }
+
+    pub async fn delete(&self, user_id: Uuid, token_id: Uuid) -> Result<(), Error> {
+        self.store.delete(key(user_id, token_id)).await?;
+
+        Ok(())
+    }
+}
+
+fn key(user_id: Uuid, token_id: Uuid) -> String {
+    format!("{}.{}", user_id, token_id)
+}
+
+fn hash(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
@@ -0,0 +1,265 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_with::{
+    base64::{Base64, UrlSafe},
+    formats::Unpadded,
+    serde_as,
+};
+
+use crate::app::{error::FieldError, validation::Validate};
+
+// Registration ceremony, sent to `/complete`
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterPublicKeyCredential {
+    pub id: String,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub raw_id: Vec<u8>,
+    #[serde(rename = "type")]
+    pub type_: String,
+    // Forwarded as sent, authenticators add fields such as `publicKey` that aren't typed here
+    pub response: Value,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorAttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub client_data_json: Vec<u8>,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub attestation_object: Vec<u8>,
+}
+
+impl Validate for RegisterPublicKeyCredential {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = credential(&self.id, &self.raw_id, &self.type_);
+
+        let response: AuthenticatorAttestationResponse = match response(&self.response) {
+            Ok(response) => response,
+            Err(err) => {
+                errors.push(err);
+                return errors;
+            }
+        };
+
+        errors.extend(client_data(&response.client_data_json, "webauthn.create"));
+
+        if response.attestation_object.is_empty() {
+            errors.push(FieldError::new(
+                "response.attestationObject",
+                "must not be empty",
+            ));
+        }
+
+        errors
+    }
+}
+
+// Authentication ceremony, sent to `/login`
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredential {
+    pub id: String,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub raw_id: Vec<u8>,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub response: Value,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorAssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub client_data_json: Vec<u8>,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub authenticator_data: Vec<u8>,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub signature: Vec<u8>,
+}
+
+impl Validate for PublicKeyCredential {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = credential(&self.id, &self.raw_id, &self.type_);
+
+        let response: AuthenticatorAssertionResponse = match response(&self.response) {
+            Ok(response) => response,
+            Err(err) => {
+                errors.push(err);
+                return errors;
+            }
+        };
+
+        errors.extend(client_data(&response.client_data_json, "webauthn.get"));
+
+        // rpIdHash (32) + flags (1) + signCount (4)
+        if response.authenticator_data.len() < 37 {
+            errors.push(FieldError::new(
+                "response.authenticatorData",
+                "must be at least 37 bytes",
+            ));
+        }
+
+        if response.signature.is_empty() {
+            errors.push(FieldError::new("response.signature", "must not be empty"));
+        }
+
+        errors
+    }
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    type_: String,
+    challenge: String,
+    origin: String,
+}
+
+fn response<T: DeserializeOwned>(response: &Value) -> Result<T, FieldError> {
+    serde_path_to_error::deserialize(response)
+        .map_err(|err| FieldError::new(format!("response.{}", err.path()), err.inner().to_string()))
+}
+
+fn credential(id: &str, raw_id: &[u8], type_: &str) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    if type_ != "public-key" {
+        errors.push(FieldError::new("type", "must be public-key"));
+    }
+
+    if raw_id.is_empty() {
+        errors.push(FieldError::new("rawId", "must not be empty"));
+    // Base64url fields are decoded with or without padding, so `id` may carry it too
+    } else if URL_SAFE_NO_PAD.encode(raw_id) != id.trim_end_matches('=') {
+        errors.push(FieldError::new("id", "must match rawId"));
+    }
+
+    errors
+}
+
+fn client_data(client_data_json: &[u8], ceremony: &str) -> Vec<FieldError> {
+    let field = "response.clientDataJSON";
+
+    let Ok(client_data) = serde_json::from_slice::<ClientData>(client_data_json) else {
+        return vec![FieldError::new(field, "must be a client data JSON")];
+    };
+
+    let mut errors = vec![];
+
+    if client_data.type_ != ceremony {
+        errors.push(FieldError::new(field, format!("type must be {}", ceremony)));
+    }
+
+    if client_data.challenge.is_empty() {
+        errors.push(FieldError::new(field, "challenge must not be empty"));
+    }
+
+    if client_data.origin.is_empty() {
+        errors.push(FieldError::new(field, "origin must not be empty"));
+    }
+
+    errors
+}
+
+// Options returned by `/join`, either to register a new passkey or to use an existing one
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum ChallengeResponse {
+    Creation(CreationChallengeResponse),
+    Request(RequestChallengeResponse),
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationChallengeResponse {
+    pub public_key: CreationOptions,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CreationOptions {
+    pub rp: RelyingParty,
+    pub user: UserEntity,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub challenge: Vec<u8>,
+    pub pub_key_cred_params: Vec<PubKeyCredParam>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RelyingParty {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub id: Vec<u8>,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct PubKeyCredParam {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub alg: i64,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestChallengeResponse {
+    pub public_key: RequestOptions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mediation: Option<String>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestOptions {
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub challenge: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    pub rp_id: String,
+    #[serde(default)]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_verification: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde_as(as = "Base64<UrlSafe, Unpadded>")]
+    pub id: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transports: Option<Vec<String>>,
+}
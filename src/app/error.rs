@@ -1,8 +1,11 @@
 use axum::{
+    extract::multipart::MultipartError,
     http::StatusCode,
     response::{IntoResponse, Response},
+    Json,
 };
 use axum_extra::typed_header::TypedHeaderRejection;
+use serde::Serialize;
 use tracing::error;
 
 impl IntoResponse for AppError {
@@ -10,8 +13,18 @@ impl IntoResponse for AppError {
         error!("{}", self.to_string());
 
         match self {
+            AppError::Validation(errors) => {
+                return (
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    Json(ValidationErrors { errors }),
+                )
+                    .into_response()
+            }
+            AppError::Forbidden => (StatusCode::FORBIDDEN, "".to_string()),
+            AppError::Conflict => (StatusCode::CONFLICT, "".to_string()),
             AppError::Status(status) => match status.code() {
                 tonic::Code::InvalidArgument => (StatusCode::UNPROCESSABLE_ENTITY, "".to_string()),
+                tonic::Code::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, "".to_string()),
                 code => (StatusCode::BAD_REQUEST, code.to_string()),
             },
             error => (StatusCode::BAD_REQUEST, error.to_string()),
@@ -20,24 +33,59 @@ impl IntoResponse for AppError {
     }
 }
 
+impl From<tonic::Status> for AppError {
+    fn from(status: tonic::Status) -> Self {
+        AppError::Status(Box::new(status))
+    }
+}
+
 impl From<uuid::Error> for AppError {
     fn from(error: uuid::Error) -> Self {
         AppError::Other(flux_lib::error::Error::new(error))
     }
 }
 
+#[derive(Serialize, Debug)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum AppError {
     #[error("there is not entity")]
     NoEntity,
+    #[error("forbidden")]
+    Forbidden,
+    #[error("conflict")]
+    Conflict,
+    #[error("validation failed: {0:?}")]
+    Validation(Vec<FieldError>),
+    // Boxed, a bare status makes every `Result<_, AppError>` several hundred bytes large
     #[error(transparent)]
-    Status(#[from] tonic::Status),
+    Status(Box<tonic::Status>),
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
     #[error(transparent)]
     Decode(#[from] prost::DecodeError),
     #[error(transparent)]
     Auth(#[from] TypedHeaderRejection),
+    #[error(transparent)]
+    Multipart(#[from] MultipartError),
     #[error("RECV")]
     Recv(#[from] tokio::sync::broadcast::error::RecvError),
     #[error(transparent)]
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use flux_messages_api::get_streams_response;
+use flux_users_api::{get_users_response, me_response, update_me_response};
+use serde::Serialize;
+
+use super::error::AppError;
+
+pub(super) mod settings;
+pub(super) mod state;
+
+pub type Users = HashMap<String, User>;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct User {
+    pub user_id: String,
+    pub name: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub abbr: String,
+    pub color: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Stream {
+    pub stream_id: String,
+    pub message_id: String,
+    pub text: Option<String>,
+    pub users: Vec<User>,
+}
+
+impl TryFrom<(get_streams_response::Stream, &Users)> for Stream {
+    type Error = AppError;
+
+    fn try_from(
+        (stream, users): (get_streams_response::Stream, &Users),
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            stream_id: stream.stream_id().into(),
+            message_id: stream.message_id().into(),
+            users: stream
+                .user_ids
+                .iter()
+                .map(|user_id| users.get(user_id).cloned().ok_or(AppError::NoEntity))
+                .collect::<Result<Vec<User>, Self::Error>>()?,
+            text: stream.text,
+        })
+    }
+}
+
+impl From<get_users_response::User> for User {
+    fn from(user: get_users_response::User) -> Self {
+        Self {
+            user_id: user.user_id().into(),
+            name: user.name().into(),
+            first_name: user.first_name().into(),
+            last_name: user.last_name().into(),
+            abbr: user.abbr().into(),
+            color: user.color().into(),
+        }
+    }
+}
+
+impl From<me_response::User> for User {
+    fn from(user: me_response::User) -> Self {
+        Self {
+            user_id: user.user_id().into(),
+            name: user.name().into(),
+            first_name: user.first_name().into(),
+            last_name: user.last_name().into(),
+            abbr: user.abbr().into(),
+            color: user.color().into(),
+        }
+    }
+}
+
+impl From<update_me_response::User> for User {
+    fn from(user: update_me_response::User) -> Self {
+        Self {
+            user_id: user.user_id().into(),
+            name: user.name().into(),
+            first_name: user.first_name().into(),
+            last_name: user.last_name().into(),
+            abbr: user.abbr().into(),
+            color: user.color().into(),
+        }
+    }
+}
+
+impl From<flux_notify_api::User> for User {
+    fn from(user: flux_notify_api::User) -> Self {
+        Self {
+            user_id: user.user_id().into(),
+            name: user.name().into(),
+            first_name: user.first_name().into(),
+            last_name: user.last_name().into(),
+            abbr: user.abbr().into(),
+            color: user.color().into(),
+        }
+    }
+}
+
+impl From<flux_notify_api::message::User> for User {
+    fn from(user: flux_notify_api::message::User) -> Self {
+        Self {
+            user_id: user.user_id().into(),
+            name: user.name().into(),
+            first_name: user.first_name().into(),
+            last_name: user.last_name().into(),
+            abbr: user.abbr().into(),
+            color: user.color().into(),
+        }
+    }
+}
+
+impl From<User> for flux_notify_api::message::User {
+    fn from(user: User) -> Self {
+        Self {
+            user_id: Some(user.user_id),
+            name: Some(user.name),
+            first_name: Some(user.first_name),
+            last_name: Some(user.last_name),
+            abbr: Some(user.abbr),
+            color: Some(user.color),
+        }
+    }
+}
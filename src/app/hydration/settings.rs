@@ -0,0 +1,18 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct HydrationSettings {
+    pub users: UsersCacheSettings,
+    pub streams: StreamsSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UsersCacheSettings {
+    pub capacity: u64,
+    pub ttl: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct StreamsSettings {
+    pub chunk: usize,
+}
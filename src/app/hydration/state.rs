@@ -0,0 +1,124 @@
+use std::{collections::HashSet, time::Duration};
+
+use flux_messages_api::{streams_service_client::StreamsServiceClient, GetStreamsRequest};
+use flux_users_api::{users_service_client::UsersServiceClient, GetUsersRequest};
+use futures::future;
+use moka::future::Cache;
+use tonic::transport::Channel;
+use uuid::Uuid;
+
+use crate::app::{error::AppError, rpc, settings::AppSettings};
+
+use super::{Stream, User, Users};
+
+#[derive(Clone)]
+pub struct HydrationState {
+    users_service_client: UsersServiceClient<Channel>,
+    streams_service_client: StreamsServiceClient<Channel>,
+    deadline: u64,
+    chunk: usize,
+    users: Cache<String, User>,
+}
+
+impl HydrationState {
+    pub fn new(
+        settings: &AppSettings,
+        users_service_client: UsersServiceClient<Channel>,
+        streams_service_client: StreamsServiceClient<Channel>,
+    ) -> Self {
+        let users = Cache::builder()
+            .max_capacity(settings.hydration.users.capacity)
+            .time_to_live(Duration::from_secs(settings.hydration.users.ttl))
+            .build();
+
+        Self {
+            users_service_client,
+            streams_service_client,
+            deadline: settings.clients.deadline,
+            chunk: settings.hydration.streams.chunk.max(1),
+            users,
+        }
+    }
+
+    // Ids are deduplicated and only cache misses reach the users service
+    pub async fn users(&self, user_ids: &[String]) -> Result<Users, AppError> {
+        let mut users = Users::new();
+        let mut missing = vec![];
+
+        for user_id in user_ids.iter().collect::<HashSet<&String>>() {
+            match self.users.get(user_id).await {
+                Some(user) => {
+                    users.insert(user_id.clone(), user);
+                }
+                None => missing.push(user_id.clone()),
+            }
+        }
+
+        if !missing.is_empty() {
+            let res = rpc::deadline(
+                self.deadline,
+                self.users_service_client
+                    .clone()
+                    .get_users(GetUsersRequest { user_ids: missing }),
+            )
+            .await?;
+
+            for user in res.users {
+                let user = User::from(user);
+
+                self.users.insert(user.user_id.clone(), user.clone()).await;
+                users.insert(user.user_id.clone(), user);
+            }
+        }
+
+        Ok(users)
+    }
+
+    pub async fn user(&self, user_id: Uuid) -> Result<User, AppError> {
+        self.users(&[user_id.into()])
+            .await?
+            .into_values()
+            .next()
+            .ok_or(AppError::NoEntity)
+    }
+
+    // Streams keep the order returned by the streams service. Chunks are fetched concurrently,
+    // so users of one chunk are fetched while streams of the others are still in flight
+    pub async fn streams(&self, stream_ids: Vec<String>) -> Result<Vec<Stream>, AppError> {
+        let chunks = future::try_join_all(
+            stream_ids
+                .chunks(self.chunk)
+                .map(|stream_ids| self.streams_chunk(stream_ids.to_vec())),
+        )
+        .await?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    async fn streams_chunk(&self, stream_ids: Vec<String>) -> Result<Vec<Stream>, AppError> {
+        let res = rpc::deadline(
+            self.deadline,
+            self.streams_service_client
+                .clone()
+                .get_streams(GetStreamsRequest { stream_ids }),
+        )
+        .await?;
+
+        let user_ids: Vec<String> = res
+            .streams
+            .iter()
+            .flat_map(|stream| stream.user_ids.iter().cloned())
+            .collect();
+
+        let users = self.users(&user_ids).await?;
+
+        res.streams
+            .into_iter()
+            .map(|stream| (stream, &users).try_into())
+            .collect()
+    }
+
+    pub async fn invalidate(&self, user_id: &str) {
+        self.users.invalidate(user_id).await;
+    }
+}
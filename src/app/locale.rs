@@ -11,7 +11,7 @@ pub struct AppLocale {
 
 impl fmt::Display for AppLocale {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.locale.to_string())
+        write!(f, "{}", self.locale)
     }
 }
 
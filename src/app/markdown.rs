@@ -0,0 +1,192 @@
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash as _, Hasher as _},
+    sync::Arc,
+    time::Duration,
+};
+
+use ammonia::Builder;
+use moka::future::Cache;
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+use serde::Deserialize;
+
+use settings::MarkdownSettings;
+
+pub(super) mod settings;
+
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Text,
+    Html,
+}
+
+#[derive(Clone)]
+pub struct MarkdownState {
+    cache: Cache<String, Rendered>,
+    sanitizer: Arc<Builder<'static>>,
+}
+
+#[derive(Clone)]
+struct Rendered {
+    hash: u64,
+    html: String,
+}
+
+impl MarkdownState {
+    pub fn new(settings: &MarkdownSettings) -> Self {
+        let cache = Cache::builder()
+            .max_capacity(settings.capacity)
+            .time_to_live(Duration::from_secs(settings.ttl))
+            .build();
+
+        let mut sanitizer = Builder::default();
+        sanitizer
+            .tags(HashSet::from([
+                "p",
+                "br",
+                "em",
+                "strong",
+                "del",
+                "code",
+                "pre",
+                "a",
+                "ul",
+                "ol",
+                "li",
+                "blockquote",
+            ]))
+            .generic_attributes(HashSet::new())
+            .tag_attributes([("a", HashSet::from(["href"]))].into())
+            .url_schemes(HashSet::from(["http", "https", "mailto"]))
+            .link_rel(Some("noopener noreferrer nofollow"));
+
+        Self {
+            cache,
+            sanitizer: Arc::new(sanitizer),
+        }
+    }
+
+    // Cached per message, an edited text no longer matches the stored hash and is rendered again
+    pub async fn render(&self, message_id: &str, text: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        if let Some(rendered) = self.cache.get(message_id).await {
+            if rendered.hash == hash {
+                return rendered.html;
+            }
+        }
+
+        let html = self.html(text);
+
+        self.cache
+            .insert(
+                message_id.into(),
+                Rendered {
+                    hash,
+                    html: html.clone(),
+                },
+            )
+            .await;
+
+        html
+    }
+
+    // The subset covers inline emphasis, code, links, lists and quotes. Headings become
+    // paragraphs, images become links and raw HTML is shown as typed
+    fn html(&self, text: &str) -> String {
+        let events =
+            Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH).map(|event| match event {
+                Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+                Event::SoftBreak => Event::HardBreak,
+                Event::Start(Tag::Heading { .. }) => Event::Start(Tag::Paragraph),
+                Event::End(TagEnd::Heading(_)) => Event::End(TagEnd::Paragraph),
+                Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }),
+                Event::End(TagEnd::Image) => Event::End(TagEnd::Link),
+                event => event,
+            });
+
+        let mut unsafe_html = String::new();
+        html::push_html(&mut unsafe_html, events);
+
+        self.sanitizer.clean(&unsafe_html).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown() -> MarkdownState {
+        MarkdownState::new(&MarkdownSettings {
+            capacity: 10,
+            ttl: 60,
+        })
+    }
+
+    #[test]
+    fn html_block_escaped() {
+        let html = markdown().html("<script>alert(1)</script>");
+
+        assert_eq!(html, "&lt;script&gt;alert(1)&lt;/script&gt;");
+    }
+
+    #[test]
+    fn inline_html_escaped() {
+        let html = markdown().html("hi <b>bold</b>");
+
+        assert_eq!(html, "<p>hi &lt;b&gt;bold&lt;/b&gt;</p>\n");
+    }
+
+    #[test]
+    fn javascript_link() {
+        let html = markdown().html("[click](javascript:alert(1))");
+
+        assert!(!html.contains("javascript"));
+        assert_eq!(
+            html,
+            "<p><a rel=\"noopener noreferrer nofollow\">click</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn image_link() {
+        let html = markdown().html("![cat](https://example.com/cat.png)");
+
+        assert!(!html.contains("<img"));
+        assert_eq!(
+            html,
+            "<p><a href=\"https://example.com/cat.png\" rel=\"noopener noreferrer nofollow\">cat</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn heading_paragraph() {
+        let html = markdown().html("# Title");
+
+        assert_eq!(html, "<p>Title</p>\n");
+    }
+
+    #[test]
+    fn link_rel() {
+        let html = markdown().html("[flux](https://example.com)");
+
+        assert_eq!(
+            html,
+            "<p><a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">flux</a></p>\n"
+        );
+    }
+}
@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct MarkdownSettings {
+    pub capacity: u64,
+    pub ttl: u64,
+}
@@ -1,117 +1,214 @@
+use std::collections::{HashMap, HashSet};
+
 use axum::{
     extract::{Path, Query, State},
-    routing::{get, post},
-    Json, Router,
+    http::{header, HeaderMap},
+    response::IntoResponse,
+    routing::{delete, get, patch, post, put},
+    Extension, Json, Router,
 };
 use create_message::Request;
-use flux_messages_api::{CreateMessageRequest, GetMessageRequest, GetStreamsRequest};
-use flux_users_api::GetUsersRequest;
+use flux_lib::error::Error;
+use flux_messages_api::{
+    get_message_response, messages_service_client::MessagesServiceClient,
+    streams_service_client::StreamsServiceClient, CreateMessageRequest, CreateReactionRequest,
+    DeleteMessageRequest, DeleteReactionRequest, GetMessageRequest, GetUserStreamsRequest,
+    UpdateMessageRequest,
+};
+use futures::future;
+use serde_json::Value;
+use tonic::transport::Channel;
+use tracing::error;
 use uuid::Uuid;
 
 use crate::app::locale::AppLocale;
+use export::Export;
+use idempotency::Claim;
+use mentions::Mention;
+use search::Search;
+
+use super::{
+    attachments::state::{AttachmentUrls, AttachmentsState},
+    error::{AppError, FieldError},
+    hydration::{state::HydrationState, Stream, User, Users},
+    markdown::{Format, MarkdownState},
+    pagination::Pagination,
+    rpc,
+    state::AppState,
+    user::{AppUser, Scope},
+    validation,
+};
 
-use super::{error::AppError, state::AppState, user::AppUser};
+mod export;
+pub(super) mod idempotency;
+mod mentions;
+mod messaging;
+pub(super) mod search;
+pub(super) mod settings;
+mod text;
+
+pub(super) use batch::Error as BatchError;
 
 pub fn router() -> Router<AppState> {
     Router::new()
-        .route("/{message_id}", get(get_message))
-        .route("/", post(create_message))
+        .route(
+            "/search",
+            get(search_messages).layer(Extension(Scope::MessagesRead)),
+        )
+        .route("/batch", post(batch).layer(Extension(Scope::MessagesRead)))
+        .route(
+            "/{message_id}",
+            get(get_message).layer(Extension(Scope::MessagesRead)),
+        )
+        .route(
+            "/{message_id}",
+            patch(update_message).layer(Extension(Scope::MessagesWrite)),
+        )
+        .route(
+            "/{message_id}",
+            delete(delete_message).layer(Extension(Scope::MessagesWrite)),
+        )
+        .route(
+            "/",
+            post(create_message).layer(Extension(Scope::MessagesWrite)),
+        )
+        .route(
+            "/{message_id}/export",
+            get(export_message).layer(Extension(Scope::MessagesRead)),
+        )
+        .route(
+            "/{message_id}/reactions/{emoji}",
+            put(create_reaction).layer(Extension(Scope::MessagesWrite)),
+        )
+        .route(
+            "/{message_id}/reactions/{emoji}",
+            delete(delete_reaction).layer(Extension(Scope::MessagesWrite)),
+        )
+}
+
+pub async fn messaging(state: &AppState) -> Result<(), Error> {
+    tokio::spawn(messaging::search(state.clone()));
+
+    Ok(())
 }
 
 async fn get_message(
     Path(message_id): Path<Uuid>,
     State(AppState {
-        messages_service_client,
-        users_service_client,
-        streams_service_client,
+        mut messages_service_client,
+        hydration,
+        attachments,
+        markdown,
+        settings,
         ..
     }): State<AppState>,
+    user: Option<AppUser>,
     Query(req): Query<get_message::Request>,
+    Query(pagination): Query<Pagination>,
 ) -> Result<Json<get_message::Response>, AppError> {
-    // TODO: make requests not seq
-
-    let get_message_response = messages_service_client
-        .clone()
-        .get_message(GetMessageRequest {
-            message_id: Some(message_id.into()),
-            cursor_message_id: if let Some(cursor_message_id) = req.cursor_message_id {
-                Some(cursor_message_id.into())
-            } else {
-                None
-            },
-            limit: None,
-        })
-        .await?
-        .into_inner();
-
-    let mut get_streams_request = GetStreamsRequest::default();
+    let deadline = settings.clients.deadline;
+    let limit = pagination.limit(&settings.messages.pagination)?;
 
-    for message in &get_message_response.messages {
-        if let Some(stream_id) = message.stream_id.clone() {
-            get_streams_request.stream_ids.push(stream_id);
-        }
-    }
+    rpc::budget(deadline, async {
+        // `cursor_message_id` is kept as an alias of `before` for older clients
+        let get_message_response = rpc::deadline(
+            deadline,
+            messages_service_client.get_message(GetMessageRequest {
+                message_id: Some(message_id.into()),
+                cursor_message_id: pagination
+                    .before
+                    .or(req.cursor_message_id)
+                    .map(|v| v.into()),
+                after_message_id: pagination.after.map(|v| v.into()),
+                limit: Some(limit),
+                user_id: user.map(|user| user.id.into()),
+            }),
+        )
+        .await?;
 
-    if let Some(message) = get_message_response.message.clone() {
-        if let Some(stream_id) = message.stream_id {
-            get_streams_request.stream_ids.push(stream_id);
-        }
-    }
+        let messages: Vec<&get_message_response::Message> = get_message_response
+            .message
+            .iter()
+            .chain(get_message_response.messages.iter())
+            .collect();
 
-    let get_streams_response = streams_service_client
-        .clone()
-        .get_streams(get_streams_request)
-        .await?
-        .into_inner();
+        let markdown = (req.format == Format::Html).then_some(&markdown);
+        let hydrated = hydrate(&hydration, &attachments, markdown, &messages).await?;
 
-    let mut user_ids: Vec<String> = vec![];
+        Ok(Json((get_message_response, &hydrated).try_into()?))
+    })
+    .await
+}
 
-    if let Some(message) = &get_message_response.message {
-        user_ids.push(message.user_id().into());
-    }
+async fn export_message(
+    Path(message_id): Path<Uuid>,
+    State(AppState {
+        messages_service_client,
+        hydration,
+        markdown,
+        settings,
+        ..
+    }): State<AppState>,
+    user: Option<AppUser>,
+    Query(req): Query<export_message::Request>,
+) -> Result<impl IntoResponse, AppError> {
+    let export = Export {
+        messages_service_client,
+        hydration,
+        markdown,
+        deadline: settings.clients.deadline,
+        limit: settings.messages.pagination.max_limit,
+        message_id: message_id.into(),
+        user_id: user.map(|user| user.id.into()),
+        format: req.format,
+    };
 
-    for message in &get_message_response.messages {
-        user_ids.push(message.user_id().into());
+    let first = export.page(None).await?;
+    if first.message.is_none() {
+        return Err(AppError::NoEntity);
     }
 
-    for stream in &get_streams_response.streams {
-        user_ids.extend(stream.user_ids.clone());
-    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, req.format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"flux-thread-{}.{}\"",
+                    message_id,
+                    req.format.extension()
+                ),
+            ),
+        ],
+        export.body(first),
+    ))
+}
 
-    user_ids.dedup();
+mod export_message {
+    use serde::Deserialize;
 
-    let get_users_response = users_service_client
-        .clone()
-        .get_users(GetUsersRequest { user_ids })
-        .await?
-        .into_inner();
+    use super::export::Format;
 
-    Ok(Json(
-        (
-            get_message_response,
-            get_users_response,
-            get_streams_response,
-        )
-            .try_into()?,
-    ))
+    #[derive(Deserialize, Debug)]
+    pub struct Request {
+        pub format: Format,
+    }
 }
 
 mod get_message {
-    use std::collections::HashMap;
-
-    use flux_lib::error::Error;
-    use flux_messages_api::{
-        get_message_response, get_streams_response, GetMessageResponse, GetStreamsResponse,
-    };
-    use flux_users_api::{get_users_response, GetUsersResponse};
+    use flux_messages_api::GetMessageResponse;
     use serde::{Deserialize, Serialize};
     use uuid::Uuid;
 
-    use crate::app::error::AppError;
+    use crate::app::{error::AppError, markdown::Format, pagination::Cursors};
+
+    use super::{Hydrated, Message};
 
     #[derive(Deserialize, Debug)]
     pub struct Request {
         pub cursor_message_id: Option<Uuid>,
+        #[serde(default)]
+        pub format: Format,
     }
 
     #[derive(Serialize)]
@@ -119,186 +216,621 @@ mod get_message {
         message: Message,
         messages: Vec<Message>,
         cursor_message_id: Option<String>,
+        #[serde(flatten)]
+        cursors: Cursors,
     }
 
-    #[derive(Serialize)]
-    struct Stream {
-        stream_id: String,
-        message_id: String,
-        text: Option<String>,
-        users: Vec<User>,
-    }
-
-    #[derive(Serialize)]
-    struct Message {
-        message_id: String,
-        stream: Option<Stream>,
-        text: String,
-        code: String,
-        user: User,
-        order: i64,
-    }
-
-    #[derive(Serialize)]
-    struct User {
-        user_id: String,
-        name: String,
-        first_name: String,
-        last_name: String,
-        abbr: String,
-        color: String,
-    }
-
-    type Users = HashMap<String, get_users_response::User>;
-    type Streams = HashMap<String, get_streams_response::Stream>;
-
-    impl TryFrom<(GetMessageResponse, GetUsersResponse, GetStreamsResponse)> for Response {
+    impl TryFrom<(GetMessageResponse, &Hydrated)> for Response {
         type Error = AppError;
 
         fn try_from(
-            (get_message_response, get_users_response, get_streams_response): (
-                GetMessageResponse,
-                GetUsersResponse,
-                GetStreamsResponse,
-            ),
+            (get_message_response, hydrated): (GetMessageResponse, &Hydrated),
         ) -> Result<Self, Self::Error> {
-            let users: Users = get_users_response
-                .users
-                .into_iter()
-                .map(|v| (v.user_id().into(), v))
-                .collect();
-
-            let streams: Streams = get_streams_response
-                .streams
-                .into_iter()
-                .map(|v| (v.stream_id().into(), v))
-                .collect();
-
             let message = get_message_response.message.ok_or(AppError::NoEntity)?;
 
             Ok(Self {
-                message: (message.clone(), &users, streams.get(message.stream_id())).try_into()?,
+                message: (message, hydrated).try_into()?,
 
                 messages: get_message_response
                     .messages
                     .into_iter()
-                    .map(|m| -> Result<Message, Self::Error> {
-                        (m.clone(), &users, streams.get(m.stream_id())).try_into()
-                    })
+                    .map(|m| -> Result<Message, Self::Error> { (m, hydrated).try_into() })
                     .collect::<Result<Vec<Message>, Self::Error>>()?,
 
+                cursors: Cursors {
+                    next: get_message_response.cursor_message_id.clone(),
+                    prev: get_message_response.prev_message_id,
+                },
                 cursor_message_id: get_message_response.cursor_message_id,
             })
         }
     }
+}
 
-    impl
-        TryFrom<(
-            get_message_response::Message,
-            &Users,
-            Option<&get_streams_response::Stream>,
-        )> for Message
-    {
-        type Error = AppError;
+async fn search_messages(
+    State(AppState {
+        messages_service_client,
+        streams_service_client,
+        hydration,
+        attachments,
+        search,
+        settings,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    Query(req): Query<search_messages::Request>,
+) -> Result<Json<search_messages::Response>, AppError> {
+    let deadline = settings.clients.deadline;
 
-        fn try_from(
-            (message, users, stream): (
-                get_message_response::Message,
-                &Users,
-                Option<&get_streams_response::Stream>,
-            ),
-        ) -> Result<Self, Self::Error> {
-            let user = users
-                .get(&message.user_id().to_string())
-                .ok_or(AppError::NoEntity)?
-                .to_owned();
+    let q = req.q.trim();
+    if q.is_empty() {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "q",
+            "must not be empty",
+        )]));
+    }
 
-            Ok(Self {
-                message_id: message.message_id().into(),
-                text: message.text().into(),
-                code: message.code().into(),
-                user: user.into(),
-                stream: match stream {
-                    Some(stream) => Some((stream.to_owned(), users).try_into()?),
-                    None => None,
-                },
-                order: message.order(),
-            })
+    let user_stream_ids = user_stream_ids(
+        &streams_service_client,
+        deadline,
+        settings.messages.search.streams_limit,
+        &user,
+    )
+    .await?;
+
+    let stream_ids: Vec<String> = match req.stream_id {
+        Some(stream_id) => user_stream_ids
+            .into_iter()
+            .filter(|user_stream_id| *user_stream_id == stream_id.to_string())
+            .collect(),
+        None => user_stream_ids.into_iter().collect(),
+    };
+
+    let hits = search
+        .search(Search {
+            q: q.into(),
+            stream_ids,
+            user_id: req.user_id.map(|v| v.into()),
+            limit: req
+                .limit
+                .unwrap_or(settings.messages.search.limit)
+                .clamp(1, settings.messages.search.max_limit),
+        })
+        .await?;
+
+    let message_ids: Vec<String> = hits.iter().map(|hit| hit.message_id.clone()).collect();
+    let responses = fetch_messages(
+        &messages_service_client,
+        deadline,
+        Some(&user),
+        &message_ids,
+    )
+    .await;
+
+    // The index may lag behind deletions, so messages that are already gone are skipped
+    let mut found = vec![];
+    for (hit, res) in hits.into_iter().zip(responses) {
+        match res {
+            Ok(message) => found.push((message, hit.snippet)),
+            Err(err) if batch::Error::of(&err) == Some(batch::Error::NotFound) => {}
+            Err(err) => return Err(err),
         }
     }
 
-    impl TryFrom<(get_streams_response::Stream, &Users)> for Stream {
-        type Error = Error;
+    let messages: Vec<&get_message_response::Message> =
+        found.iter().map(|(message, _)| message).collect();
 
-        fn try_from(
-            (stream, users): (get_streams_response::Stream, &Users),
-        ) -> Result<Self, Self::Error> {
-            Ok(Self {
-                stream_id: stream.stream_id().into(),
-                message_id: stream.message_id().into(),
-                text: stream.text,
-                users: stream
-                    .user_ids
-                    .iter()
-                    .map(|user_id| -> Result<User, Error> {
-                        let user = users
-                            .get(&user_id.to_string())
-                            .ok_or(AppError::NoEntity)?
-                            .to_owned();
-
-                        Ok(User {
-                            user_id: user.user_id().into(),
-                            name: user.name().into(),
-                            first_name: user.first_name().into(),
-                            last_name: user.last_name().into(),
-                            abbr: user.abbr().into(),
-                            color: user.color().into(),
-                        })
+    let hydrated = hydrate(&hydration, &attachments, None, &messages).await?;
+
+    Ok(Json(search_messages::Response {
+        results: found
+            .into_iter()
+            .map(
+                |(message, snippet)| -> Result<search_messages::Hit, AppError> {
+                    Ok(search_messages::Hit {
+                        message: (message, &hydrated).try_into()?,
+                        snippet,
                     })
-                    .collect::<Result<Vec<User>, Self::Error>>()?,
-            })
+                },
+            )
+            .collect::<Result<Vec<search_messages::Hit>, AppError>>()?,
+    }))
+}
+
+// One result per id and in the same order, fetched concurrently
+pub(super) async fn fetch_messages(
+    messages_service_client: &MessagesServiceClient<Channel>,
+    deadline: u64,
+    user: Option<&AppUser>,
+    message_ids: &[String],
+) -> Vec<Result<get_message_response::Message, AppError>> {
+    future::join_all(message_ids.iter().map(|message_id| {
+        let mut messages_service_client = messages_service_client.clone();
+        let req = GetMessageRequest {
+            message_id: Some(message_id.clone()),
+            cursor_message_id: None,
+            after_message_id: None,
+            limit: Some(0),
+            user_id: user.map(|user| user.id.into()),
+        };
+
+        async move {
+            rpc::deadline(deadline, messages_service_client.get_message(req))
+                .await?
+                .message
+                .ok_or(AppError::NoEntity)
         }
+    }))
+    .await
+}
+
+async fn batch(
+    State(AppState {
+        messages_service_client,
+        hydration,
+        attachments,
+        markdown,
+        settings,
+        ..
+    }): State<AppState>,
+    user: Option<AppUser>,
+    Json(data): Json<Value>,
+) -> Result<Json<batch::Response>, AppError> {
+    let req: batch::Request = validation::parse(data)?;
+
+    if req.message_ids.len() > settings.messages.batch.max_ids {
+        return Err(AppError::Validation(vec![FieldError::new(
+            "message_ids",
+            format!(
+                "must contain at most {} ids",
+                settings.messages.batch.max_ids
+            ),
+        )]));
     }
 
-    impl From<get_users_response::User> for User {
-        fn from(user: get_users_response::User) -> Self {
-            Self {
-                user_id: user.user_id().into(),
-                name: user.name().into(),
-                first_name: user.first_name().into(),
-                last_name: user.last_name().into(),
-                abbr: user.abbr().into(),
-                color: user.color().into(),
+    let mut seen = HashSet::new();
+    let message_ids: Vec<String> = req
+        .message_ids
+        .iter()
+        .filter(|message_id| seen.insert(**message_id))
+        .map(|message_id| message_id.to_string())
+        .collect();
+
+    let responses = fetch_messages(
+        &messages_service_client,
+        settings.clients.deadline,
+        user.as_ref(),
+        &message_ids,
+    )
+    .await;
+
+    let mut found = vec![];
+    let mut errors = vec![];
+    for (message_id, res) in message_ids.into_iter().zip(responses) {
+        match res {
+            Ok(message) => found.push(message),
+            Err(err) => errors.push(batch::MessageError {
+                message_id,
+                error: batch::Error::of(&err).ok_or(err)?,
+            }),
+        }
+    }
+
+    let messages: Vec<&get_message_response::Message> = found.iter().collect();
+
+    let markdown = (req.format == Format::Html).then_some(&markdown);
+    let hydrated = hydrate(&hydration, &attachments, markdown, &messages).await?;
+
+    let mut res = batch::Response {
+        messages: vec![],
+        errors,
+    };
+
+    // A message whose author or stream can no longer be resolved is reported like a missing one
+    for message in found {
+        let message_id = message.message_id().to_string();
+
+        match (message, &hydrated).try_into() {
+            Ok(message) => res.messages.push(message),
+            Err(err) => res.errors.push(batch::MessageError {
+                message_id,
+                error: batch::Error::of(&err).ok_or(err)?,
+            }),
+        }
+    }
+
+    Ok(Json(res))
+}
+
+mod batch {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::app::{
+        error::{AppError, FieldError},
+        markdown::Format,
+        validation::Validate,
+    };
+
+    use super::Message;
+
+    #[derive(Deserialize, Debug)]
+    pub struct Request {
+        pub message_ids: Vec<Uuid>,
+        #[serde(default)]
+        pub format: Format,
+    }
+
+    impl Validate for Request {
+        fn validate(&self) -> Vec<FieldError> {
+            if self.message_ids.is_empty() {
+                return vec![FieldError::new("message_ids", "must not be empty")];
+            }
+
+            vec![]
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub messages: Vec<Message>,
+        pub errors: Vec<MessageError>,
+    }
+
+    #[derive(Serialize)]
+    pub struct MessageError {
+        pub message_id: String,
+        pub error: Error,
+    }
+
+    #[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Error {
+        NotFound,
+        Forbidden,
+    }
+
+    impl Error {
+        // Errors that concern a single message, anything else fails the whole batch
+        pub fn of(err: &AppError) -> Option<Self> {
+            match err {
+                AppError::NoEntity => Some(Self::NotFound),
+                AppError::Forbidden => Some(Self::Forbidden),
+                AppError::Status(status) => match status.code() {
+                    tonic::Code::NotFound => Some(Self::NotFound),
+                    tonic::Code::PermissionDenied => Some(Self::Forbidden),
+                    _ => None,
+                },
+                _ => None,
             }
         }
     }
 }
 
+// Participation is paged by the streams service, so every page is walked
+async fn user_stream_ids(
+    streams_service_client: &StreamsServiceClient<Channel>,
+    deadline: u64,
+    limit: i64,
+    user: &AppUser,
+) -> Result<HashSet<String>, AppError> {
+    let mut stream_ids = HashSet::new();
+    let mut before_stream_id = None;
+
+    loop {
+        let res = rpc::deadline(
+            deadline,
+            streams_service_client
+                .clone()
+                .get_user_streams(GetUserStreamsRequest {
+                    user_id: Some(user.id.into()),
+                    limit: Some(limit),
+                    before_stream_id,
+                    after_stream_id: None,
+                }),
+        )
+        .await?;
+
+        stream_ids.extend(res.stream_ids);
+
+        match res.next_stream_id {
+            Some(next_stream_id) => before_stream_id = Some(next_stream_id),
+            None => break,
+        }
+    }
+
+    Ok(stream_ids)
+}
+
+mod search_messages {
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use super::Message;
+
+    #[derive(Deserialize, Debug)]
+    pub struct Request {
+        pub q: String,
+        pub stream_id: Option<Uuid>,
+        pub user_id: Option<Uuid>,
+        pub limit: Option<usize>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub results: Vec<Hit>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Hit {
+        pub message: Message,
+        pub snippet: String,
+    }
+}
+
+struct Hydrated {
+    users: Users,
+    streams: HashMap<String, Stream>,
+    attachments: HashMap<String, AttachmentUrls>,
+    html: HashMap<String, String>,
+}
+
+// Messages are rendered to HTML only when a markdown state is given
+async fn hydrate(
+    hydration: &HydrationState,
+    attachments: &AttachmentsState,
+    markdown: Option<&MarkdownState>,
+    messages: &[&get_message_response::Message],
+) -> Result<Hydrated, AppError> {
+    // Replies are also hydrated with the stream they are posted to, their mentions resolve there
+    let stream_ids: Vec<String> = messages
+        .iter()
+        .flat_map(|message| [message.stream_id.clone(), message.parent_stream_id.clone()])
+        .flatten()
+        .collect::<HashSet<String>>()
+        .into_iter()
+        .collect();
+
+    let user_ids: Vec<String> = messages
+        .iter()
+        .map(|message| message.user_id().into())
+        .collect();
+
+    let attachment_ids: Vec<String> = messages
+        .iter()
+        .flat_map(|message| message.attachment_ids.clone())
+        .collect();
+
+    // Authors and attachments do not depend on streams, so they are fetched alongside
+    let (streams, users, attachments) = tokio::try_join!(
+        hydration.streams(stream_ids),
+        hydration.users(&user_ids),
+        async {
+            attachments
+                .hydrate(&attachment_ids)
+                .await
+                .map_err(AppError::from)
+        },
+    )?;
+
+    let mut html = HashMap::new();
+    if let Some(markdown) = markdown {
+        for message in messages {
+            html.insert(
+                message.message_id().into(),
+                markdown.render(message.message_id(), message.text()).await,
+            );
+        }
+    }
+
+    Ok(Hydrated {
+        users,
+        streams: streams
+            .into_iter()
+            .map(|stream| (stream.stream_id.clone(), stream))
+            .collect(),
+        attachments,
+        html,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct Message {
+    message_id: String,
+    stream: Option<Stream>,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html: Option<String>,
+    code: String,
+    user: User,
+    order: i64,
+    reactions: Vec<Reaction>,
+    attachments: Vec<AttachmentUrls>,
+    mentions: Vec<Mention>,
+}
+
+impl TryFrom<(get_message_response::Message, &Hydrated)> for Message {
+    type Error = AppError;
+
+    fn try_from(
+        (message, hydrated): (get_message_response::Message, &Hydrated),
+    ) -> Result<Self, Self::Error> {
+        let user = hydrated
+            .users
+            .get(message.user_id())
+            .ok_or(AppError::NoEntity)?
+            .to_owned();
+
+        let stream = hydrated.streams.get(message.stream_id()).cloned();
+
+        // Same participants as `mentions::notify`, those of the parent's stream
+        let mentions = message
+            .parent_stream_id
+            .as_ref()
+            .and_then(|parent_stream_id| hydrated.streams.get(parent_stream_id))
+            .map(|parent_stream| mentions::resolve(message.text(), &parent_stream.users))
+            .unwrap_or_default();
+
+        Ok(Self {
+            message_id: message.message_id().into(),
+            mentions,
+            html: hydrated.html.get(message.message_id()).cloned(),
+            text: message.text().into(),
+            code: message.code().into(),
+            user,
+            stream,
+            order: message.order(),
+            reactions: message.reactions.into_iter().map(Into::into).collect(),
+            attachments: message
+                .attachment_ids
+                .iter()
+                .filter_map(|attachment_id| hydrated.attachments.get(attachment_id).cloned())
+                .collect(),
+        })
+    }
+}
+
 async fn create_message(
-    State(AppState {
-        messages_service_client,
-        ..
-    }): State<AppState>,
+    State(state): State<AppState>,
     user: AppUser,
     locale: AppLocale,
-    Json(req): Json<Request>,
+    headers: HeaderMap,
+    Json(data): Json<Value>,
 ) -> Result<Json<create_message::Response>, AppError> {
+    let mut req: Request = validation::deserialize(data)?;
+    req.normalize();
+    req.validate(&state.settings.messages.validation)?;
+
+    let Some(idempotency_key) = idempotency_key(&headers)? else {
+        return Ok(Json(create(&state, &user, locale, req).await?));
+    };
+
+    let hash = idempotency::hash(&req)?;
+
+    match state
+        .idempotency
+        .claim(user.id, &idempotency_key, &hash)
+        .await?
+    {
+        Claim::Acquired => {}
+        Claim::Replay(res) => return Ok(Json(res)),
+        Claim::Conflict => return Err(AppError::Conflict),
+    }
+
+    match create(&state, &user, locale, req).await {
+        // The message exists at this point, so failing to record it must not fail the request
+        Ok(res) => {
+            if let Err(err) = state
+                .idempotency
+                .complete(user.id, &idempotency_key, &hash, &res)
+                .await
+            {
+                error!("idempotency: {}", err);
+            }
+
+            Ok(Json(res))
+        }
+        // `create` only fails before the message exists, so the key can be claimed again
+        Err(err) => {
+            state.idempotency.release(user.id, &idempotency_key).await?;
+
+            Err(err)
+        }
+    }
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(idempotency::HEADER) else {
+        return Ok(None);
+    };
+
+    match value.to_str() {
+        Ok(value) if !value.is_empty() && value.len() <= 255 => Ok(Some(value.into())),
+        _ => Err(AppError::Validation(vec![FieldError::new(
+            "Idempotency-Key",
+            "must be 1 to 255 visible ASCII characters",
+        )])),
+    }
+}
+
+async fn create(
+    state: &AppState,
+    user: &AppUser,
+    locale: AppLocale,
+    req: Request,
+) -> Result<create_message::Response, AppError> {
+    let AppState {
+        messages_service_client,
+        attachments,
+        ..
+    } = state;
+
+    let mut errors = vec![];
+
+    for (i, attachment_id) in req.attachment_ids.iter().enumerate() {
+        match attachments.get(*attachment_id).await? {
+            Some(attachment)
+                if attachment.user_id == user.id && attachment.message_id.is_none() => {}
+            _ => errors.push(FieldError::new(
+                format!("attachment_ids[{}]", i),
+                "must be an uploaded attachment",
+            )),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    // Only replies can mention, the participants are those of the parent's stream
+    let mentioned = req
+        .message_id
+        .filter(|_| mentions::has_mentions(&req.text))
+        .map(|parent_message_id| (parent_message_id, req.text.clone()));
+    let attachment_ids = req.attachment_ids;
+
     let res = messages_service_client
         .clone()
         .create_message(CreateMessageRequest {
             text: Some(req.text),
-            message_id: match req.message_id {
-                Some(message_id) => Some(message_id.into()),
-                None => None,
-            },
+            message_id: req.message_id.map(|message_id| message_id.into()),
             code: Some(req.code),
             user_id: Some(user.id.into()),
             locale: Some(locale.to_string()),
+            attachment_ids: attachment_ids
+                .iter()
+                .map(|attachment_id| attachment_id.to_string())
+                .collect(),
         })
         .await?
         .into_inner();
 
-    Ok(Json(res.into()))
+    // The message exists from here on, so failing to link attachments must not fail the request
+    let attached = match Uuid::parse_str(res.message_id()) {
+        Ok(message_id) => attachments
+            .attach(&attachment_ids, message_id)
+            .await
+            .map_err(AppError::from),
+        Err(err) => Err(err.into()),
+    };
+
+    if let Err(err) = attached {
+        error!("attachments: {}", err);
+    }
+
+    if let Some((parent_message_id, text)) = mentioned {
+        tokio::spawn(mentions::run(
+            state.clone(),
+            mentions::Request {
+                message_id: res.message_id().into(),
+                parent_message_id: parent_message_id.into(),
+                text,
+                user_id: user.id,
+            },
+        ));
+    }
+
+    Ok(res.into())
 }
 
 mod create_message {
@@ -306,19 +838,50 @@ mod create_message {
     use serde::{Deserialize, Serialize};
     use uuid::Uuid;
 
-    #[derive(Deserialize, Debug)]
+    use crate::app::{
+        error::{AppError, FieldError},
+        messages::{settings::ValidationSettings, text},
+    };
+
+    #[derive(Deserialize, Serialize, Debug)]
     pub struct Request {
         pub text: String,
         pub code: String,
         pub message_id: Option<Uuid>,
+        #[serde(default)]
+        pub attachment_ids: Vec<Uuid>,
     }
 
-    #[derive(Serialize)]
+    impl Request {
+        pub fn normalize(&mut self) {
+            self.text = text::normalize(&self.text);
+        }
+
+        // Every field is checked before failing, so all errors are returned at once
+        pub fn validate(&self, settings: &ValidationSettings) -> Result<(), AppError> {
+            let mut errors = text::validate(&self.text, settings);
+
+            if !settings.code_pattern.is_match(&self.code) {
+                errors.push(FieldError::new(
+                    "code",
+                    format!("must match {}", settings.code_pattern),
+                ));
+            }
+
+            if !errors.is_empty() {
+                return Err(AppError::Validation(errors));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
     pub struct Response {
         pub message: Message,
     }
 
-    #[derive(Serialize)]
+    #[derive(Serialize, Deserialize)]
     pub struct Message {
         pub message_id: String,
     }
@@ -333,3 +896,374 @@ mod create_message {
         }
     }
 }
+
+pub(super) async fn find_message(
+    messages_service_client: &MessagesServiceClient<Channel>,
+    message_id: Uuid,
+    user: &AppUser,
+) -> Result<get_message_response::Message, AppError> {
+    let message = messages_service_client
+        .clone()
+        .get_message(GetMessageRequest {
+            message_id: Some(message_id.into()),
+            cursor_message_id: None,
+            limit: Some(0),
+            user_id: Some(user.id.into()),
+            after_message_id: None,
+        })
+        .await?
+        .into_inner()
+        .message
+        .ok_or(AppError::NoEntity)?;
+
+    Ok(message)
+}
+
+// Only the author may change a message
+async fn get_own_message(
+    messages_service_client: &MessagesServiceClient<Channel>,
+    message_id: Uuid,
+    user: &AppUser,
+) -> Result<get_message_response::Message, AppError> {
+    let message = find_message(messages_service_client, message_id, user).await?;
+
+    if message.user_id() != user.id.to_string() {
+        return Err(AppError::Forbidden);
+    }
+
+    Ok(message)
+}
+
+async fn update_message(
+    Path(message_id): Path<Uuid>,
+    State(AppState {
+        messages_service_client,
+        hydration,
+        settings,
+        js,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    Json(data): Json<Value>,
+) -> Result<Json<update_message::Response>, AppError> {
+    let req: update_message::Request = validation::deserialize(data)?;
+    let text = text::normalize(&req.text);
+
+    let errors = text::validate(&text, &settings.messages.validation);
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    // The author for the event is looked up while ownership is checked
+    let (_, author) = tokio::try_join!(
+        get_own_message(&messages_service_client, message_id, &user),
+        hydration.user(user.id),
+    )?;
+
+    let message = messages_service_client
+        .clone()
+        .update_message(UpdateMessageRequest {
+            message_id: Some(message_id.into()),
+            text: Some(text),
+        })
+        .await?
+        .into_inner()
+        .message
+        .ok_or(AppError::NoEntity)?;
+
+    messaging::message_updated(
+        &js,
+        &settings,
+        update_message::event_message(&message, author),
+    )
+    .await?;
+
+    Ok(Json(message.into()))
+}
+
+mod update_message {
+    use flux_messages_api::get_message_response;
+    use serde::{Deserialize, Serialize};
+
+    use crate::app::hydration::User;
+
+    #[derive(Deserialize, Debug)]
+    pub struct Request {
+        pub text: String,
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub message: Message,
+    }
+
+    #[derive(Serialize)]
+    pub struct Message {
+        pub message_id: String,
+        pub text: String,
+        pub code: String,
+        pub order: i64,
+    }
+
+    impl From<get_message_response::Message> for Response {
+        fn from(message: get_message_response::Message) -> Self {
+            Self {
+                message: Message {
+                    message_id: message.message_id().into(),
+                    text: message.text().into(),
+                    code: message.code().into(),
+                    order: message.order(),
+                },
+            }
+        }
+    }
+
+    pub fn event_message(
+        message: &get_message_response::Message,
+        user: User,
+    ) -> flux_notify_api::Message {
+        flux_notify_api::Message {
+            message_id: message.message_id.clone(),
+            text: message.text.clone(),
+            code: message.code.clone(),
+            order: message.order,
+            user: Some(user.into()),
+            stream_id: message.stream_id.clone(),
+        }
+    }
+}
+
+async fn delete_message(
+    Path(message_id): Path<Uuid>,
+    State(AppState {
+        messages_service_client,
+        settings,
+        js,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<delete_message::Response>, AppError> {
+    let message = get_own_message(&messages_service_client, message_id, &user).await?;
+
+    messages_service_client
+        .clone()
+        .delete_message(DeleteMessageRequest {
+            message_id: Some(message_id.into()),
+        })
+        .await?;
+
+    messaging::message_deleted(
+        &js,
+        &settings,
+        flux_notify_api::MessageDeleted {
+            message_id: message.message_id,
+            stream_id: message.stream_id,
+        },
+    )
+    .await?;
+
+    Ok(Json(delete_message::Response {}))
+}
+
+mod delete_message {
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    pub struct Response {}
+}
+
+// Reactions are stored in the fully-qualified form, so variants of one emoji are counted together
+fn emoji(emoji: &str) -> Result<&'static str, AppError> {
+    emojis::get(emoji)
+        .map(|emoji| emoji.as_str())
+        .ok_or_else(|| AppError::Validation(vec![FieldError::new("emoji", "must be an emoji")]))
+}
+
+async fn create_reaction(
+    Path((message_id, emoji)): Path<(Uuid, String)>,
+    State(AppState {
+        messages_service_client,
+        settings,
+        js,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<reaction::Response>, AppError> {
+    let emoji = self::emoji(&emoji)?;
+
+    let message = find_message(&messages_service_client, message_id, &user).await?;
+
+    let res = messages_service_client
+        .clone()
+        .create_reaction(CreateReactionRequest {
+            message_id: Some(message_id.into()),
+            user_id: Some(user.id.into()),
+            emoji: Some(emoji.into()),
+        })
+        .await?
+        .into_inner();
+
+    messaging::reaction(
+        &js,
+        &settings,
+        reaction::event(&message, &user, emoji, &res.reactions, true),
+    )
+    .await?;
+
+    Ok(Json(res.reactions.into()))
+}
+
+async fn delete_reaction(
+    Path((message_id, emoji)): Path<(Uuid, String)>,
+    State(AppState {
+        messages_service_client,
+        settings,
+        js,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+) -> Result<Json<reaction::Response>, AppError> {
+    let emoji = self::emoji(&emoji)?;
+
+    let message = find_message(&messages_service_client, message_id, &user).await?;
+
+    let res = messages_service_client
+        .clone()
+        .delete_reaction(DeleteReactionRequest {
+            message_id: Some(message_id.into()),
+            user_id: Some(user.id.into()),
+            emoji: Some(emoji.into()),
+        })
+        .await?
+        .into_inner();
+
+    messaging::reaction(
+        &js,
+        &settings,
+        reaction::event(&message, &user, emoji, &res.reactions, false),
+    )
+    .await?;
+
+    Ok(Json(res.reactions.into()))
+}
+
+mod reaction {
+    use flux_messages_api::get_message_response;
+    use serde::Serialize;
+
+    use crate::app::user::AppUser;
+
+    use super::Reaction;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        reactions: Vec<Reaction>,
+    }
+
+    impl From<Vec<flux_messages_api::Reaction>> for Response {
+        fn from(reactions: Vec<flux_messages_api::Reaction>) -> Self {
+            Self {
+                reactions: reactions.into_iter().map(Into::into).collect(),
+            }
+        }
+    }
+
+    pub fn event(
+        message: &get_message_response::Message,
+        user: &AppUser,
+        emoji: &str,
+        reactions: &[flux_messages_api::Reaction],
+        added: bool,
+    ) -> flux_notify_api::Reaction {
+        flux_notify_api::Reaction {
+            message_id: message.message_id.clone(),
+            stream_id: stream_id(message),
+            user_id: Some(user.id.into()),
+            emoji: Some(emoji.into()),
+            count: Some(
+                reactions
+                    .iter()
+                    .find(|reaction| reaction.emoji() == emoji)
+                    .map(|reaction| reaction.count())
+                    .unwrap_or_default(),
+            ),
+            added: Some(added),
+        }
+    }
+
+    // A reply belongs to the stream of its parent, only a root message is shown in its own stream
+    fn stream_id(message: &get_message_response::Message) -> Option<String> {
+        message
+            .parent_stream_id
+            .clone()
+            .or_else(|| message.stream_id.clone())
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Reaction {
+    emoji: String,
+    count: i64,
+    reacted: bool,
+}
+
+impl From<flux_messages_api::Reaction> for Reaction {
+    fn from(reaction: flux_messages_api::Reaction) -> Self {
+        Self {
+            emoji: reaction.emoji().into(),
+            count: reaction.count(),
+            reacted: reaction.reacted(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(
+        stream_id: Option<&str>,
+        parent_stream_id: Option<&str>,
+    ) -> get_message_response::Message {
+        get_message_response::Message {
+            message_id: Some("1".into()),
+            stream_id: stream_id.map(Into::into),
+            parent_stream_id: parent_stream_id.map(Into::into),
+            ..Default::default()
+        }
+    }
+
+    fn user() -> AppUser {
+        AppUser {
+            id: Uuid::nil(),
+            jti: None,
+        }
+    }
+
+    #[test]
+    fn reaction_reply_stream() {
+        let event = reaction::event(&message(None, Some("parent")), &user(), "👍", &[], true);
+
+        assert_eq!(event.stream_id.as_deref(), Some("parent"));
+    }
+
+    #[test]
+    fn reaction_thread_reply_stream() {
+        let event = reaction::event(
+            &message(Some("thread"), Some("parent")),
+            &user(),
+            "👍",
+            &[],
+            true,
+        );
+
+        assert_eq!(event.stream_id.as_deref(), Some("parent"));
+    }
+
+    #[test]
+    fn reaction_root_stream() {
+        let event = reaction::event(&message(Some("root"), None), &user(), "👍", &[], false);
+
+        assert_eq!(event.stream_id.as_deref(), Some("root"));
+    }
+}
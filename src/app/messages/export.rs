@@ -0,0 +1,209 @@
+use axum::body::Body;
+use flux_messages_api::{
+    get_message_response, messages_service_client::MessagesServiceClient, GetMessageRequest,
+    GetMessageResponse,
+};
+use futures::{stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use tonic::transport::Channel;
+
+use crate::app::{
+    error::AppError,
+    hydration::{state::HydrationState, User},
+    markdown::MarkdownState,
+    rpc,
+};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Md,
+    Html,
+    Json,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Md => "text/markdown; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Md => "md",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Export {
+    pub messages_service_client: MessagesServiceClient<Channel>,
+    pub hydration: HydrationState,
+    pub markdown: MarkdownState,
+    pub deadline: u64,
+    pub limit: i64,
+    pub message_id: String,
+    pub user_id: Option<String>,
+    pub format: Format,
+}
+
+#[derive(Serialize)]
+struct Message {
+    message_id: String,
+    order: i64,
+    text: String,
+    user: User,
+}
+
+enum Next {
+    Page(Box<GetMessageResponse>),
+    Cursor(String),
+}
+
+impl Export {
+    pub async fn page(
+        &self,
+        cursor_message_id: Option<String>,
+    ) -> Result<GetMessageResponse, AppError> {
+        rpc::deadline(
+            self.deadline,
+            self.messages_service_client
+                .clone()
+                .get_message(GetMessageRequest {
+                    message_id: Some(self.message_id.clone()),
+                    cursor_message_id,
+                    after_message_id: None,
+                    limit: Some(self.limit),
+                    user_id: self.user_id.clone(),
+                }),
+        )
+        .await
+    }
+
+    // Pages are fetched and rendered one at a time while the body is written, so a long
+    // thread is never held in memory as a whole. The first page is fetched by the caller,
+    // which lets a missing thread fail before the response starts
+    pub fn body(self, first: GetMessageResponse) -> Body {
+        let header = self.header();
+        let footer = self.footer();
+
+        let pages = stream::unfold(
+            (Some(Next::Page(Box::new(first))), 0),
+            move |(next, count)| {
+                let export = self.clone();
+
+                async move {
+                    let (res, first) = match next? {
+                        Next::Page(res) => (*res, true),
+                        Next::Cursor(cursor_message_id) => {
+                            match export.page(Some(cursor_message_id)).await {
+                                Ok(res) => (res, false),
+                                Err(err) => return Some((Err(err), (None, count))),
+                            }
+                        }
+                    };
+
+                    let next = match &res.cursor_message_id {
+                        Some(cursor_message_id) if !res.messages.is_empty() => {
+                            Some(Next::Cursor(cursor_message_id.clone()))
+                        }
+                        _ => None,
+                    };
+
+                    let messages: Vec<get_message_response::Message> = if first {
+                        res.message.into_iter().chain(res.messages).collect()
+                    } else {
+                        res.messages
+                    };
+
+                    match export.chunk(messages, count).await {
+                        Ok((chunk, count)) => Some((Ok(chunk), (next, count))),
+                        Err(err) => Some((Err(err), (None, count))),
+                    }
+                }
+            },
+        );
+
+        Body::from_stream(
+            stream::once(async move { Ok(header) })
+                .chain(pages)
+                .chain(stream::once(async move { Ok::<String, AppError>(footer) })),
+        )
+    }
+
+    fn header(&self) -> String {
+        match self.format {
+            Format::Md => format!("# Thread {}\n\n", self.message_id),
+            Format::Html => format!(
+                "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Thread {}</title>\n</head>\n<body>\n",
+                self.message_id
+            ),
+            Format::Json => "[".into(),
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self.format {
+            Format::Md => "".into(),
+            Format::Html => "</body>\n</html>\n".into(),
+            Format::Json => "]\n".into(),
+        }
+    }
+
+    async fn chunk(
+        &self,
+        messages: Vec<get_message_response::Message>,
+        mut count: usize,
+    ) -> Result<(String, usize), AppError> {
+        let user_ids: Vec<String> = messages
+            .iter()
+            .map(|message| message.user_id().into())
+            .collect();
+
+        let users = self.hydration.users(&user_ids).await?;
+
+        let mut chunk = String::new();
+
+        for message in messages {
+            let message = Message {
+                message_id: message.message_id().into(),
+                order: message.order(),
+                user: users
+                    .get(message.user_id())
+                    .cloned()
+                    .ok_or(AppError::NoEntity)?,
+                text: message.text.unwrap_or_default(),
+            };
+
+            match self.format {
+                Format::Md => chunk.push_str(&format!(
+                    "**{}** · #{}\n\n{}\n\n---\n\n",
+                    message.user.name, message.order, message.text
+                )),
+                Format::Html => chunk.push_str(&format!(
+                    "<article>\n<header><strong>{}</strong> #{}</header>\n{}</article>\n",
+                    ammonia::clean_text(&message.user.name),
+                    message.order,
+                    self.markdown
+                        .render(&message.message_id, &message.text)
+                        .await
+                )),
+                Format::Json => {
+                    if count > 0 {
+                        chunk.push(',');
+                    }
+                    chunk.push_str(&serde_json::to_string(&message)?);
+                }
+            }
+
+            count += 1;
+        }
+
+        Ok((chunk, count))
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use async_nats::jetstream::kv::{self, CreateErrorKind};
+use flux_lib::error::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
+
+use crate::app::{auth::sessions, AppJS};
+
+use super::settings::IdempotencySettings;
+
+pub const HEADER: &str = "idempotency-key";
+
+#[derive(Clone)]
+pub struct MessagesIdempotency {
+    store: kv::Store,
+    lease: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    hash: String,
+    response: Option<Value>,
+    #[serde(default)]
+    leased_at: u64,
+}
+
+pub enum Claim<T> {
+    Acquired,
+    Replay(T),
+    Conflict,
+}
+
+impl MessagesIdempotency {
+    pub async fn new(js: &AppJS, settings: &IdempotencySettings) -> Result<Self, Error> {
+        let store = js
+            .create_key_value(kv::Config {
+                bucket: settings.bucket.clone(),
+                max_age: Duration::from_secs(settings.max_age),
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Self {
+            store,
+            lease: settings.lease,
+        })
+    }
+
+    // The key is reserved atomically, so concurrent retries on other replicas see it as taken.
+    // A reservation is leased, so a request dropped mid-flight does not block retries for max_age
+    pub async fn claim<T: DeserializeOwned>(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        hash: &str,
+    ) -> Result<Claim<T>, Error> {
+        let value = serde_json::to_vec(&Record {
+            hash: hash.into(),
+            response: None,
+            leased_at: sessions::now(),
+        })?;
+
+        let key = key(user_id, idempotency_key);
+
+        match self.store.create(&key, value.clone().into()).await {
+            Ok(_) => return Ok(Claim::Acquired),
+            Err(err) if err.kind() == CreateErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let Some(entry) = self.store.entry(&key).await? else {
+            return Ok(Claim::Conflict);
+        };
+
+        let record: Record = serde_json::from_slice(&entry.value)?;
+
+        // A different body under the same key cannot be replayed
+        if record.hash != hash {
+            return Ok(Claim::Conflict);
+        }
+
+        if let Some(response) = record.response {
+            return Ok(Claim::Replay(serde_json::from_value(response)?));
+        }
+
+        if record.leased_at + self.lease > sessions::now() {
+            return Ok(Claim::Conflict);
+        }
+
+        // Only one retry can take over a stale lease, the others lose the revision race
+        Ok(
+            match self.store.update(&key, value.into(), entry.revision).await {
+                Ok(_) => Claim::Acquired,
+                Err(_) => Claim::Conflict,
+            },
+        )
+    }
+
+    pub async fn complete<T: Serialize>(
+        &self,
+        user_id: Uuid,
+        idempotency_key: &str,
+        hash: &str,
+        response: &T,
+    ) -> Result<(), Error> {
+        let record = Record {
+            hash: hash.into(),
+            response: Some(serde_json::to_value(response)?),
+            leased_at: sessions::now(),
+        };
+
+        self.store
+            .put(
+                key(user_id, idempotency_key),
+                serde_json::to_vec(&record)?.into(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // Failed requests free the key so the client can retry them
+    pub async fn release(&self, user_id: Uuid, idempotency_key: &str) -> Result<(), Error> {
+        self.store.delete(key(user_id, idempotency_key)).await?;
+
+        Ok(())
+    }
+}
+
+pub fn hash<T: Serialize>(req: &T) -> Result<String, Error> {
+    Ok(hex::encode(Sha256::digest(serde_json::to_vec(req)?)))
+}
+
+// Client keys are arbitrary strings, so they are hashed into a valid KV key
+fn key(user_id: Uuid, idempotency_key: &str) -> String {
+    format!(
+        "{}.{}",
+        user_id,
+        hex::encode(Sha256::digest(idempotency_key.as_bytes()))
+    )
+}
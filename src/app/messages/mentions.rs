@@ -0,0 +1,255 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use flux_messages_api::GetMessageRequest;
+use flux_notify_api::SendWebPushRequest;
+use regex::Regex;
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::app::{error::AppError, hydration::User, rpc, state::AppState};
+
+use super::messaging;
+
+// A reference must not follow a word character, so e-mail addresses are not mentions
+static MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^\w@])@(\w+(?:[.-]\w+)*)").unwrap());
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Mention {
+    pub user_id: String,
+    pub name: String,
+    // Position of the `@name` reference in characters
+    pub offset: usize,
+    pub length: usize,
+}
+
+pub fn has_mentions(text: &str) -> bool {
+    MENTION.is_match(text)
+}
+
+// Only participants of the stream can be mentioned. A reference matches the full name with
+// `.`, `-` or `_` in place of spaces, like `@ada.lovelace`, or the first name alone, like `@ada`.
+// Full names win over first names, and a reference that still fits several users is left as is
+pub fn resolve(text: &str, users: &[User]) -> Vec<Mention> {
+    MENTION
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let name = captures.get(1)?;
+            let reference = handle(name.as_str());
+
+            let user = unique(users, |user| handle(&user.name) == reference)
+                .or_else(|| unique(users, |user| handle(&user.first_name) == reference))?;
+
+            Some(Mention {
+                user_id: user.user_id.clone(),
+                name: user.name.clone(),
+                offset: text[..name.start() - 1].chars().count(),
+                length: name.as_str().chars().count() + 1,
+            })
+        })
+        .collect()
+}
+
+fn handle(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '.' || c == '-' || c == '_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join(".")
+}
+
+fn unique(users: &[User], matches: impl Fn(&User) -> bool) -> Option<&User> {
+    let mut found = users.iter().filter(|user| matches(user));
+
+    match (found.next(), found.next()) {
+        (Some(user), None) => Some(user),
+        _ => None,
+    }
+}
+
+pub struct Request {
+    pub message_id: String,
+    pub parent_message_id: String,
+    pub text: String,
+    pub user_id: Uuid,
+}
+
+pub async fn run(state: AppState, req: Request) {
+    if let Err(err) = notify(&state, req).await {
+        error!("{}", err);
+    }
+}
+
+// A reply mentions participants of the stream it is posted to, which belongs to its parent.
+// Mentioned users get a targeted event, and a web push when no socket of theirs is open
+async fn notify(state: &AppState, req: Request) -> Result<(), AppError> {
+    let deadline = state.settings.clients.deadline;
+
+    let parent = rpc::deadline(
+        deadline,
+        state
+            .messages_service_client
+            .clone()
+            .get_message(GetMessageRequest {
+                message_id: Some(req.parent_message_id),
+                cursor_message_id: None,
+                after_message_id: None,
+                limit: Some(0),
+                user_id: Some(req.user_id.into()),
+            }),
+    )
+    .await?
+    .message
+    .ok_or(AppError::NoEntity)?;
+
+    let Some(stream_id) = parent.stream_id.clone() else {
+        return Ok(());
+    };
+
+    let participants = state
+        .hydration
+        .streams(vec![stream_id.clone()])
+        .await?
+        .into_iter()
+        .flat_map(|stream| stream.users)
+        .collect::<Vec<User>>();
+
+    let author = state.hydration.user(req.user_id).await?;
+
+    let mentioned: HashSet<String> = resolve(&req.text, &participants)
+        .into_iter()
+        .map(|mention| mention.user_id)
+        .filter(|mentioned_user_id| *mentioned_user_id != author.user_id)
+        .collect();
+
+    // One failed delivery must not cost the other mentioned users their notification
+    for mentioned_user_id in mentioned {
+        let mention = Mentioned {
+            message_id: &req.message_id,
+            stream_id: &stream_id,
+            text: &req.text,
+            author: &author,
+        };
+
+        if let Err(err) = mention.send(state, mentioned_user_id).await {
+            error!("{}", err);
+        }
+    }
+
+    Ok(())
+}
+
+struct Mentioned<'a> {
+    message_id: &'a str,
+    stream_id: &'a str,
+    text: &'a str,
+    author: &'a User,
+}
+
+impl Mentioned<'_> {
+    async fn send(&self, state: &AppState, mentioned_user_id: String) -> Result<(), AppError> {
+        messaging::mention(
+            &state.js,
+            &state.settings,
+            flux_notify_api::Mention {
+                message_id: Some(self.message_id.into()),
+                stream_id: Some(self.stream_id.into()),
+                user_id: Some(mentioned_user_id.clone()),
+                text: Some(self.text.into()),
+                user: Some(self.author.clone().into()),
+            },
+        )
+        .await?;
+
+        let sockets = state
+            .sessions
+            .list_sockets(Uuid::parse_str(&mentioned_user_id)?)
+            .await?;
+
+        if sockets.is_empty() {
+            rpc::deadline(
+                state.settings.clients.deadline,
+                state
+                    .push_service_client
+                    .clone()
+                    .send_web_push(SendWebPushRequest {
+                        user_id: Some(mentioned_user_id),
+                        title: Some(self.author.name.clone()),
+                        body: Some(self.text.into()),
+                        message_id: Some(self.message_id.into()),
+                    }),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(user_id: &str, first_name: &str, last_name: &str) -> User {
+        User {
+            user_id: user_id.into(),
+            name: format!("{} {}", first_name, last_name),
+            first_name: first_name.into(),
+            last_name: last_name.into(),
+            abbr: String::new(),
+            color: String::new(),
+        }
+    }
+
+    #[test]
+    fn resolve_offsets() {
+        let users = vec![user("1", "Ada", "Lovelace"), user("2", "Alan", "Turing")];
+
+        let mentions = resolve("hi @ada.lovelace and @Alan", &users);
+
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].user_id, "1");
+        assert_eq!((mentions[0].offset, mentions[0].length), (3, 13));
+        assert_eq!(mentions[1].user_id, "2");
+        assert_eq!((mentions[1].offset, mentions[1].length), (21, 5));
+    }
+
+    #[test]
+    fn resolve_offsets_in_characters() {
+        let users = vec![user("1", "Zoë", "Brown")];
+
+        let mentions = resolve("ünïcödé @zoë_brown", &users);
+
+        assert_eq!(mentions.len(), 1);
+        assert_eq!((mentions[0].offset, mentions[0].length), (8, 10));
+    }
+
+    #[test]
+    fn resolve_at_start() {
+        let users = vec![user("1", "Ada", "Lovelace")];
+
+        let mentions = resolve("@ada-lovelace, look", &users);
+
+        assert_eq!(mentions.len(), 1);
+        assert_eq!((mentions[0].offset, mentions[0].length), (0, 13));
+    }
+
+    #[test]
+    fn resolve_skips_emails_and_strangers() {
+        let users = vec![user("1", "Ada", "Lovelace")];
+
+        assert!(resolve("mail ada@lovelace.org or @grace", &users).is_empty());
+    }
+
+    #[test]
+    fn resolve_ambiguous_first_name() {
+        let users = vec![user("1", "Ada", "Lovelace"), user("2", "Ada", "Byron")];
+
+        assert!(resolve("@ada", &users).is_empty());
+
+        let mentions = resolve("@ada.byron", &users);
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].user_id, "2");
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use flux_lib::error::Error;
+use flux_notify_api::event::Payload;
+use prost::Message as _;
+use tokio::time;
+use tracing::error;
+
+use crate::app::{settings::AppSettings, state::AppState, AppJS};
+
+pub async fn message_updated(
+    js: &AppJS,
+    settings: &AppSettings,
+    message: flux_notify_api::Message,
+) -> Result<(), Error> {
+    publish(
+        js,
+        settings.messages.messaging.message_updated.subject.clone(),
+        Payload::MessageUpdated(message),
+    )
+    .await
+}
+
+pub async fn message_deleted(
+    js: &AppJS,
+    settings: &AppSettings,
+    message: flux_notify_api::MessageDeleted,
+) -> Result<(), Error> {
+    publish(
+        js,
+        settings.messages.messaging.message_deleted.subject.clone(),
+        Payload::MessageDeleted(message),
+    )
+    .await
+}
+
+pub async fn reaction(
+    js: &AppJS,
+    settings: &AppSettings,
+    reaction: flux_notify_api::Reaction,
+) -> Result<(), Error> {
+    publish(
+        js,
+        settings.messages.messaging.reaction.subject.clone(),
+        Payload::Reaction(reaction),
+    )
+    .await
+}
+
+pub async fn mention(
+    js: &AppJS,
+    settings: &AppSettings,
+    mention: flux_notify_api::Mention,
+) -> Result<(), Error> {
+    publish(
+        js,
+        settings.messages.messaging.mention.subject.clone(),
+        Payload::Mention(mention),
+    )
+    .await
+}
+
+async fn publish(js: &AppJS, subject: String, payload: Payload) -> Result<(), Error> {
+    let event = flux_notify_api::Event {
+        payload: Some(payload),
+    };
+
+    js.publish(subject, event.encode_to_vec().into())
+        .await?
+        .await?;
+
+    Ok(())
+}
+
+// The index is local to each replica, so every replica replays the stream on its own ordered
+// consumer. Indexing is idempotent, and a subscription resumes after the last indexed event,
+// also across restarts
+pub async fn search(state: AppState) {
+    loop {
+        if let Err(err) = search::run(&state).await {
+            error!("search: {}", err);
+        }
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+mod search {
+    use std::time::Duration;
+
+    use async_nats::jetstream::{
+        self,
+        consumer::{pull::OrderedConfig, Consumer, DeliverPolicy},
+    };
+    use flux_lib::error::Error;
+    use flux_notify_api::event::Payload;
+    use prost::Message as _;
+    use tokio::time::{self, MissedTickBehavior};
+    use tokio_stream::StreamExt as _;
+    use tracing::error;
+
+    use crate::app::{messages::search::Document, settings::AppSettings, state::AppState, AppJS};
+
+    pub async fn run(state: &AppState) -> Result<(), Error> {
+        let AppState {
+            js,
+            settings,
+            search,
+            ..
+        } = state;
+
+        let consumer = consumer(js, settings, search.sequence()?).await?;
+        let mut messages = consumer.messages().await?;
+
+        let mut interval =
+            time::interval(Duration::from_secs(settings.messages.search.interval.get()));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                message = messages.next() => {
+                    let Some(message) = message else {
+                        break;
+                    };
+
+                    if let Err(err) = handler(state, message?).await {
+                        error!("{}", err);
+                    }
+                }
+                _ = interval.tick() => search.commit().await?,
+            }
+        }
+
+        search.commit().await
+    }
+
+    async fn consumer(
+        js: &AppJS,
+        settings: &AppSettings,
+        sequence: u64,
+    ) -> Result<Consumer<OrderedConfig>, Error> {
+        let deliver_policy = match sequence {
+            0 => DeliverPolicy::All,
+            sequence => DeliverPolicy::ByStartSequence {
+                start_sequence: sequence + 1,
+            },
+        };
+
+        Ok(js
+            .create_consumer_on_stream(
+                OrderedConfig {
+                    filter_subjects: settings.messages.search.subjects.clone(),
+                    deliver_policy,
+                    ..Default::default()
+                },
+                settings.nats.stream.clone(),
+            )
+            .await?)
+    }
+
+    async fn handler(state: &AppState, message: jetstream::Message) -> Result<(), Error> {
+        let sequence = message.info().map_err(Error::msg)?.stream_sequence;
+        let flux_notify_api::Event { payload } =
+            flux_notify_api::Event::decode(message.payload.as_ref())?;
+
+        match payload {
+            Some(Payload::Message(message)) | Some(Payload::MessageUpdated(message)) => {
+                state.search.index(message.into(), sequence).await?
+            }
+            Some(Payload::MessageDeleted(message)) => {
+                state
+                    .search
+                    .delete(message.message_id().into(), sequence)
+                    .await?
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    impl From<flux_notify_api::Message> for Document {
+        fn from(message: flux_notify_api::Message) -> Self {
+            Self {
+                message_id: message.message_id().into(),
+                stream_id: message.stream_id().into(),
+                user_id: message
+                    .user
+                    .as_ref()
+                    .map(|user| user.user_id().into())
+                    .unwrap_or_default(),
+                text: message.text().into(),
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use flux_lib::error::Error;
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    doc,
+    query::{BooleanQuery, Occur, Query, QueryParser, TermQuery, TermSetQuery},
+    schema::{Field, IndexRecordOption, Schema, Value as _, STORED, STRING, TEXT},
+    snippet::SnippetGenerator,
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+
+use super::settings::SearchSettings;
+
+#[derive(Clone)]
+pub struct MessagesSearch {
+    settings: SearchSettings,
+    index: Index,
+    reader: IndexReader,
+    writer: Arc<Mutex<Writer>>,
+    fields: Fields,
+}
+
+// Changes are committed in batches, along with the stream sequence they were read up to
+struct Writer {
+    writer: IndexWriter,
+    pending: usize,
+    sequence: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Fields {
+    message_id: Field,
+    stream_id: Field,
+    user_id: Field,
+    text: Field,
+}
+
+pub struct Document {
+    pub message_id: String,
+    pub stream_id: String,
+    pub user_id: String,
+    pub text: String,
+}
+
+pub struct Search {
+    pub q: String,
+    pub stream_ids: Vec<String>,
+    pub user_id: Option<String>,
+    pub limit: usize,
+}
+
+pub struct Hit {
+    pub message_id: String,
+    pub snippet: String,
+}
+
+impl MessagesSearch {
+    pub fn new(settings: &SearchSettings) -> Result<Self, Error> {
+        let mut schema = Schema::builder();
+
+        let fields = Fields {
+            message_id: schema.add_text_field("message_id", STRING | STORED),
+            stream_id: schema.add_text_field("stream_id", STRING | STORED),
+            user_id: schema.add_text_field("user_id", STRING | STORED),
+            text: schema.add_text_field("text", TEXT | STORED),
+        };
+
+        fs::create_dir_all(&settings.path)?;
+        let index = Index::open_or_create(MmapDirectory::open(&settings.path)?, schema.build())?;
+
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+
+        // The sequence is stored in the commit payload, so it never runs ahead of the index
+        let sequence = index
+            .load_metas()?
+            .payload
+            .and_then(|payload| payload.parse().ok())
+            .unwrap_or_default();
+
+        let writer = Arc::new(Mutex::new(Writer {
+            writer: index.writer(settings.memory)?,
+            pending: 0,
+            sequence,
+        }));
+
+        Ok(Self {
+            settings: settings.clone(),
+            index,
+            reader,
+            writer,
+            fields,
+        })
+    }
+
+    // The last stream sequence that was indexed, events after it are yet to be read
+    pub fn sequence(&self) -> Result<u64, Error> {
+        Ok(self
+            .writer
+            .lock()
+            .map_err(|_| Error::msg("search: poisoned"))?
+            .sequence)
+    }
+
+    // Replacing by message id keeps edits and redelivered events from duplicating documents
+    pub async fn index(&self, document: Document, sequence: u64) -> Result<(), Error> {
+        let search = self.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            let Fields {
+                message_id,
+                stream_id,
+                user_id,
+                text,
+            } = search.fields;
+
+            let mut writer = search
+                .writer
+                .lock()
+                .map_err(|_| Error::msg("search: poisoned"))?;
+
+            writer
+                .writer
+                .delete_term(Term::from_field_text(message_id, &document.message_id));
+            writer.writer.add_document(doc!(
+                message_id => document.message_id,
+                stream_id => document.stream_id,
+                user_id => document.user_id,
+                text => document.text,
+            ))?;
+
+            search.written(&mut writer, sequence)
+        })
+        .await?
+    }
+
+    pub async fn delete(&self, message_id: String, sequence: u64) -> Result<(), Error> {
+        let search = self.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            let mut writer = search
+                .writer
+                .lock()
+                .map_err(|_| Error::msg("search: poisoned"))?;
+
+            writer
+                .writer
+                .delete_term(Term::from_field_text(search.fields.message_id, &message_id));
+
+            search.written(&mut writer, sequence)
+        })
+        .await?
+    }
+
+    // Called on an interval, so a batch that never fills up is still made searchable
+    pub async fn commit(&self) -> Result<(), Error> {
+        let search = self.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<(), Error> {
+            let mut writer = search
+                .writer
+                .lock()
+                .map_err(|_| Error::msg("search: poisoned"))?;
+
+            commit(&mut writer)
+        })
+        .await?
+    }
+
+    fn written(&self, writer: &mut Writer, sequence: u64) -> Result<(), Error> {
+        writer.pending += 1;
+        writer.sequence = sequence;
+
+        if writer.pending >= self.settings.batch {
+            commit(writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn search(&self, search: Search) -> Result<Vec<Hit>, Error> {
+        if search.stream_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let state = self.clone();
+
+        tokio::task::spawn_blocking(move || state.query(search)).await?
+    }
+
+    fn query(&self, search: Search) -> Result<Vec<Hit>, Error> {
+        let searcher = self.reader.searcher();
+
+        // Malformed query syntax still matches on the parsable terms instead of failing
+        let (text_query, _) = QueryParser::for_index(&self.index, vec![self.fields.text])
+            .parse_query_lenient(&search.q);
+
+        let stream_ids = search
+            .stream_ids
+            .iter()
+            .map(|stream_id| Term::from_field_text(self.fields.stream_id, stream_id));
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+            (Occur::Must, text_query.box_clone()),
+            (Occur::Must, Box::new(TermSetQuery::new(stream_ids))),
+        ];
+
+        if let Some(user_id) = &search.user_id {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(self.fields.user_id, user_id),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+
+        let top_docs = searcher.search(
+            &BooleanQuery::new(clauses),
+            &TopDocs::with_limit(search.limit),
+        )?;
+
+        let mut snippets = SnippetGenerator::create(&searcher, &*text_query, self.fields.text)?;
+        snippets.set_max_num_chars(self.settings.snippet);
+
+        top_docs
+            .into_iter()
+            .map(|(_, address)| -> Result<Hit, Error> {
+                let document: TantivyDocument = searcher.doc(address)?;
+
+                Ok(Hit {
+                    message_id: document
+                        .get_first(self.fields.message_id)
+                        .and_then(|value| value.as_str())
+                        .ok_or_else(|| Error::msg("search: no message_id"))?
+                        .into(),
+                    snippet: snippets.snippet_from_doc(&document).to_html(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn commit(writer: &mut Writer) -> Result<(), Error> {
+    if writer.pending == 0 {
+        return Ok(());
+    }
+
+    let sequence = writer.sequence.to_string();
+    let mut commit = writer.writer.prepare_commit()?;
+    commit.set_payload(&sequence);
+    commit.commit()?;
+
+    writer.pending = 0;
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use std::num::NonZeroU64;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::app::pagination::PaginationSettings;
+
+#[derive(Deserialize, Clone)]
+pub struct MessagesSettings {
+    pub pagination: PaginationSettings,
+    pub messaging: MessagingSettings,
+    pub validation: ValidationSettings,
+    pub batch: BatchSettings,
+    pub idempotency: IdempotencySettings,
+    pub search: SearchSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BatchSettings {
+    pub max_ids: usize,
+}
+
+// The pattern is compiled while the settings load, so a bad one stops the start
+#[serde_as]
+#[derive(Deserialize, Clone)]
+pub struct ValidationSettings {
+    pub text_max_len: usize,
+    #[serde_as(as = "DisplayFromStr")]
+    pub code_pattern: Regex,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct SearchSettings {
+    pub path: String,
+    pub memory: usize,
+    pub limit: usize,
+    pub max_limit: usize,
+    pub snippet: usize,
+    pub streams_limit: i64,
+    pub subjects: Vec<String>,
+    pub batch: usize,
+    pub interval: NonZeroU64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct IdempotencySettings {
+    pub bucket: String,
+    pub max_age: u64,
+    pub lease: u64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessagingSettings {
+    pub message_updated: MessagingSubjectSettings,
+    pub message_deleted: MessagingSubjectSettings,
+    pub reaction: MessagingSubjectSettings,
+    pub mention: MessagingSubjectSettings,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MessagingSubjectSettings {
+    pub subject: String,
+}
@@ -0,0 +1,28 @@
+use unicode_normalization::UnicodeNormalization as _;
+
+use crate::app::error::FieldError;
+
+use super::settings::ValidationSettings;
+
+// Line breaks and tabs are content, every other control character is dropped
+pub fn normalize(text: &str) -> String {
+    text.nfc()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\t'))
+        .collect()
+}
+
+// Expects normalized text, shared by message creation and editing
+pub fn validate(text: &str, settings: &ValidationSettings) -> Vec<FieldError> {
+    let mut errors = vec![];
+
+    if text.trim().is_empty() {
+        errors.push(FieldError::new("text", "must not be empty"));
+    } else if text.chars().count() > settings.text_max_len {
+        errors.push(FieldError::new(
+            "text",
+            format!("must be at most {} characters", settings.text_max_len),
+        ));
+    }
+
+    errors
+}
@@ -6,7 +6,12 @@ use axum::{
 };
 use uuid::Uuid;
 
-use super::{error::AppError, state::AppState};
+use super::{
+    auth::sessions::{self, Client, Socket},
+    error::AppError,
+    state::AppState,
+    user::AppUser,
+};
 
 mod messaging;
 mod service;
@@ -18,13 +23,44 @@ pub fn router() -> Router<AppState> {
 }
 
 async fn notify(
-    State(AppState { notify, .. }): State<AppState>,
+    State(AppState {
+        notify,
+        sessions,
+        markdown,
+        ..
+    }): State<AppState>,
+    user: Option<AppUser>,
+    client: Client,
     wsu: WebSocketUpgrade,
 ) -> Result<impl IntoResponse, AppError> {
     let notify_id = Uuid::now_v7();
+    let user_id = user.as_ref().map(|user| user.id);
+    let jti = user.and_then(|user| user.jti);
 
     let res = wsu.on_upgrade(move |ws| async move {
-        let _ = service::notify(ws, notify.clone(), notify_id).await;
+        let service = service::notify(ws, notify.clone(), markdown, notify_id, user_id, jti);
+
+        // Sockets are listed under their session while connected
+        match user_id.zip(jti) {
+            Some((user_id, session_id)) => {
+                let socket = Socket {
+                    socket_id: notify_id,
+                    session_id,
+                    client,
+                    connected_at: sessions::now(),
+                };
+
+                tokio::select! {
+                    _ = service => {},
+                    _ = sessions.keep_socket(user_id, socket) => {},
+                }
+
+                let _ = sessions.close_socket(user_id, notify_id).await;
+            }
+            None => {
+                let _ = service.await;
+            }
+        }
     });
 
     Ok(res)
@@ -32,6 +68,7 @@ async fn notify(
 
 pub async fn messaging(state: &AppState) -> Result<(), AppError> {
     tokio::spawn(messaging::event(state.clone()));
+    tokio::spawn(messaging::users(state.clone()));
 
     Ok(())
 }
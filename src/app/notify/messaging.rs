@@ -1,4 +1,7 @@
+use std::time::Duration;
+
 use flux_lib::error::Error;
+use tokio::time;
 use tokio_stream::StreamExt as _;
 use tracing::error;
 
@@ -19,6 +22,81 @@ pub async fn event(state: AppState) -> Result<(), Error> {
     Ok(())
 }
 
+// Every replica caches users, so each one reads the events on its own ordered consumer.
+// A broken subscription resumes after the last event, so no update is missed in between
+pub async fn users(state: AppState) {
+    let mut sequence = 0;
+
+    loop {
+        if let Err(err) = users::run(&state, &mut sequence).await {
+            error!("users: {}", err);
+        }
+
+        time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+mod users {
+    use async_nats::jetstream::{
+        self,
+        consumer::{pull::OrderedConfig, Consumer, DeliverPolicy},
+    };
+    use flux_lib::error::Error;
+    use tokio_stream::StreamExt as _;
+    use tracing::error;
+
+    use crate::app::{notify::service, settings::AppSettings, state::AppState, AppJS};
+
+    pub async fn run(state: &AppState, sequence: &mut u64) -> Result<(), Error> {
+        let AppState { js, settings, .. } = state;
+
+        let consumer = consumer(js, settings, *sequence).await?;
+        let mut messages = consumer.messages().await?;
+
+        while let Some(message) = messages.next().await {
+            let message = message?;
+            *sequence = message.info().map_err(Error::msg)?.stream_sequence;
+
+            if let Err(err) = handler(state.clone(), message).await {
+                error!("{}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn consumer(
+        js: &AppJS,
+        settings: &AppSettings,
+        sequence: u64,
+    ) -> Result<Consumer<OrderedConfig>, Error> {
+        // Only updates after startup matter, the cache starts empty
+        let deliver_policy = match sequence {
+            0 => DeliverPolicy::New,
+            sequence => DeliverPolicy::ByStartSequence {
+                start_sequence: sequence + 1,
+            },
+        };
+
+        Ok(js
+            .create_consumer_on_stream(
+                OrderedConfig {
+                    filter_subjects: settings.notify.messaging.event.subjects.clone(),
+                    deliver_policy,
+                    ..Default::default()
+                },
+                settings.nats.stream.clone(),
+            )
+            .await?)
+    }
+
+    async fn handler(state: AppState, message: jetstream::Message) -> Result<(), Error> {
+        service::user_updated(state, message.try_into()?).await;
+
+        Ok(())
+    }
+}
+
 mod event {
     use async_nats::jetstream::{
         self,
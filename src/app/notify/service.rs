@@ -1,8 +1,13 @@
 use axum::extract::ws::{self, WebSocket};
+use flux_notify_api::event::Payload;
 use tracing::error;
 use uuid::Uuid;
 
-use crate::app::{error::AppError, state::AppState};
+use crate::app::{
+    error::AppError,
+    markdown::{Format, MarkdownState},
+    state::AppState,
+};
 
 use super::state::NotifyState;
 
@@ -16,11 +21,25 @@ pub async fn event(state: AppState, req: event::Request) -> Result<(), AppError>
     Ok(())
 }
 
+// Profile changes must not be served from a stale hydration cache
+pub async fn user_updated(state: AppState, req: event::Request) {
+    if let Payload::UserUpdated(user) = &req.payload {
+        state.hydration.invalidate(user.user_id()).await;
+    }
+}
+
 pub mod event {
+    use std::collections::HashSet;
+
     use flux_notify_api::event::Payload;
     use serde::Serialize;
+    use uuid::Uuid;
 
-    use crate::app::error::AppError;
+    use crate::app::{
+        error::AppError,
+        hydration::{Stream, User},
+        markdown::MarkdownState,
+    };
 
     pub struct Request {
         pub payload: Payload,
@@ -30,6 +49,42 @@ pub mod event {
     #[serde(rename_all = "snake_case")]
     pub enum Event {
         Message(Message),
+        MessageUpdated(Message),
+        MessageDeleted(MessageDeleted),
+        Reaction(Reaction),
+        Mention(Mention),
+        UserUpdated(User),
+    }
+
+    impl Event {
+        // Reactions only reach sockets subscribed to the stream of the message,
+        // mentions only reach sockets of the mentioned user
+        pub fn is_subscribed(
+            &self,
+            user_id: Option<Uuid>,
+            stream_ids: Option<&HashSet<Uuid>>,
+        ) -> bool {
+            match self {
+                Self::Mention(mention) => Uuid::parse_str(&mention.user_id)
+                    .ok()
+                    .is_some_and(|mentioned_user_id| user_id == Some(mentioned_user_id)),
+                Self::Reaction(reaction) => reaction
+                    .stream_id
+                    .as_deref()
+                    .and_then(|stream_id| Uuid::parse_str(stream_id).ok())
+                    .zip(stream_ids)
+                    .is_some_and(|(stream_id, stream_ids)| stream_ids.contains(&stream_id)),
+                _ => true,
+            }
+        }
+
+        pub async fn render(mut self, markdown: &MarkdownState) -> Self {
+            if let Self::Message(message) | Self::MessageUpdated(message) = &mut self {
+                message.html = Some(markdown.render(&message.message_id, &message.text).await);
+            }
+
+            self
+        }
     }
 
     #[derive(Debug, Clone, Serialize)]
@@ -37,27 +92,36 @@ pub mod event {
         pub message_id: String,
         pub stream: Option<Stream>,
         pub text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub html: Option<String>,
         pub code: String,
         pub user: User,
         pub order: i64,
     }
 
     #[derive(Debug, Clone, Serialize)]
-    pub struct User {
+    pub struct Reaction {
+        pub message_id: String,
+        pub stream_id: Option<String>,
+        pub user_id: String,
+        pub emoji: String,
+        pub count: i64,
+        pub added: bool,
+    }
+
+    #[derive(Debug, Clone, Serialize)]
+    pub struct Mention {
+        pub message_id: String,
+        pub stream_id: Option<String>,
         pub user_id: String,
-        pub name: String,
-        pub first_name: String,
-        pub last_name: String,
-        pub abbr: String,
-        pub color: String,
+        pub text: String,
+        pub user: User,
     }
 
     #[derive(Debug, Clone, Serialize)]
-    pub struct Stream {
-        pub stream_id: String,
+    pub struct MessageDeleted {
         pub message_id: String,
-        pub text: Option<String>,
-        pub users: Vec<User>,
+        pub stream_id: Option<String>,
     }
 
     impl TryFrom<Payload> for Event {
@@ -65,37 +129,68 @@ pub mod event {
 
         fn try_from(payload: Payload) -> Result<Self, Self::Error> {
             Ok(match payload {
-                Payload::Message(message) => message.try_into()?,
+                Payload::Message(message) => Self::Message(message.try_into()?),
+                Payload::MessageUpdated(message) => Self::MessageUpdated(message.try_into()?),
+                Payload::MessageDeleted(message) => Self::MessageDeleted(message.into()),
+                Payload::Reaction(reaction) => Self::Reaction(reaction.into()),
+                Payload::Mention(mention) => Self::Mention(mention.try_into()?),
+                Payload::UserUpdated(user) => Self::UserUpdated(user.into()),
             })
         }
     }
 
-    impl TryFrom<flux_notify_api::Message> for Event {
+    impl TryFrom<flux_notify_api::Message> for Message {
         type Error = AppError;
 
         fn try_from(message: flux_notify_api::Message) -> Result<Self, Self::Error> {
             let user = message.user.clone().ok_or(AppError::NoEntity)?;
 
-            Ok(Self::Message(Message {
+            Ok(Self {
                 message_id: message.message_id().into(),
                 stream: None,
                 text: message.text().into(),
+                html: None,
                 code: message.code().into(),
                 user: user.into(),
                 order: message.order(),
-            }))
+            })
         }
     }
 
-    impl From<flux_notify_api::message::User> for User {
-        fn from(user: flux_notify_api::message::User) -> Self {
+    impl From<flux_notify_api::Reaction> for Reaction {
+        fn from(reaction: flux_notify_api::Reaction) -> Self {
             Self {
-                user_id: user.user_id().into(),
-                name: user.name().into(),
-                first_name: user.first_name().into(),
-                last_name: user.last_name().into(),
-                abbr: user.abbr().into(),
-                color: user.color().into(),
+                message_id: reaction.message_id().into(),
+                user_id: reaction.user_id().into(),
+                emoji: reaction.emoji().into(),
+                count: reaction.count(),
+                added: reaction.added(),
+                stream_id: reaction.stream_id,
+            }
+        }
+    }
+
+    impl TryFrom<flux_notify_api::Mention> for Mention {
+        type Error = AppError;
+
+        fn try_from(mention: flux_notify_api::Mention) -> Result<Self, Self::Error> {
+            let user = mention.user.clone().ok_or(AppError::NoEntity)?;
+
+            Ok(Self {
+                message_id: mention.message_id().into(),
+                user_id: mention.user_id().into(),
+                text: mention.text().into(),
+                user: user.into(),
+                stream_id: mention.stream_id,
+            })
+        }
+    }
+
+    impl From<flux_notify_api::MessageDeleted> for MessageDeleted {
+        fn from(message: flux_notify_api::MessageDeleted) -> Self {
+            Self {
+                message_id: message.message_id().into(),
+                stream_id: message.stream_id,
             }
         }
     }
@@ -104,15 +199,30 @@ pub mod event {
 pub async fn notify(
     mut ws: WebSocket,
     notify: NotifyState,
+    markdown: MarkdownState,
     notify_id: Uuid,
+    user_id: Option<Uuid>,
+    jti: Option<Uuid>,
 ) -> Result<(), AppError> {
     let mut rx = notify.tx.subscribe();
+    let mut revoked = notify.revoked.subscribe();
+    let mut deleted = notify.deleted.subscribe();
     let streams = notify.streams;
+    let mut format = Format::Text;
 
     loop {
         tokio::select! {
             res = rx.recv() => {
                 if let Ok(event) = res {
+                    if !event.is_subscribed(user_id, streams.read().await.get(&notify_id)) {
+                        continue;
+                    }
+
+                    let event = match format {
+                        Format::Html => event.render(&markdown).await,
+                        Format::Text => event,
+                    };
+
                     let _ = ws.send(event.try_into()?).await;
                 } else {
                     continue;
@@ -120,14 +230,28 @@ pub async fn notify(
             }
             res = ws.recv() => {
                 if let Some(Ok(ws::Message::Text(message))) = res {
-                    match serde_json::from_slice::<notify::Request>(message.as_bytes()) {
-                        Ok(notify::Request::Subscribe{stream_ids}) => notify::subscribe(&streams, notify_id, stream_ids).await,
-                        _ => {},
-                    };
+                    if let Ok(notify::Request::Subscribe{stream_ids, format: subscribed_format}) =
+                        serde_json::from_slice::<notify::Request>(message.as_bytes())
+                    {
+                        format = subscribed_format;
+                        notify::subscribe(&streams, notify_id, stream_ids).await
+                    }
                 } else {
                     break;
                 }
             }
+            Ok(revoked_jti) = revoked.recv() => {
+                if jti == Some(revoked_jti) {
+                    let _ = ws.send(ws::Message::Close(None)).await;
+                    break;
+                }
+            }
+            Ok(deleted_user_id) = deleted.recv() => {
+                if user_id == Some(deleted_user_id) {
+                    let _ = ws.send(ws::Message::Close(None)).await;
+                    break;
+                }
+            }
         }
     }
 
@@ -141,14 +265,18 @@ mod notify {
     use axum::extract::ws;
     use uuid::Uuid;
 
-    use crate::app::{error::AppError, notify::state::SubscribedStreams};
+    use crate::app::{error::AppError, markdown::Format, notify::state::SubscribedStreams};
 
     use super::event::Event;
 
     #[derive(Deserialize)]
     #[serde(rename_all = "snake_case")]
     pub enum Request {
-        Subscribe { stream_ids: Vec<String> },
+        Subscribe {
+            stream_ids: Vec<String>,
+            #[serde(default)]
+            format: Format,
+        },
     }
 
     impl TryFrom<Event> for ws::Message {
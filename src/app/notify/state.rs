@@ -12,14 +12,23 @@ use super::{service::event::Event, settings::NotifySettings};
 pub struct NotifyState {
     pub tx: broadcast::Sender<Event>,
     pub streams: SubscribedStreams,
+    pub revoked: broadcast::Sender<Uuid>,
+    pub deleted: broadcast::Sender<Uuid>,
 }
 
 impl NotifyState {
     pub fn new(settings: NotifySettings) -> Self {
         let tx = broadcast::Sender::new(settings.capacity);
         let streams = SubscribedStreams::default();
+        let revoked = broadcast::Sender::new(settings.capacity);
+        let deleted = broadcast::Sender::new(settings.capacity);
 
-        Self { tx, streams }
+        Self {
+            tx,
+            streams,
+            revoked,
+            deleted,
+        }
     }
 }
 
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::error::{AppError, FieldError};
+
+#[derive(Deserialize, Clone)]
+pub struct PaginationSettings {
+    pub limit: i64,
+    pub max_limit: i64,
+}
+
+// `before` pages towards older entries and `after` towards newer ones
+#[derive(Deserialize, Debug, Default)]
+pub struct Pagination {
+    pub limit: Option<i64>,
+    pub before: Option<Uuid>,
+    pub after: Option<Uuid>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Cursors {
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+impl Pagination {
+    // Out of range limits are clamped like in the users search
+    pub fn limit(&self, settings: &PaginationSettings) -> Result<i64, AppError> {
+        if self.before.is_some() && self.after.is_some() {
+            return Err(AppError::Validation(vec![FieldError::new(
+                "after",
+                "must not be combined with before",
+            )]));
+        }
+
+        Ok(self
+            .limit
+            .unwrap_or(settings.limit)
+            .clamp(1, settings.max_limit))
+    }
+}
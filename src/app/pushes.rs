@@ -1,17 +1,21 @@
 use axum::{
     extract::State,
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use flux_notify_api::{CreateWebPushRequest, GetVapidRequest, GetWebPushesRequest};
 
-use super::{error::AppError, state::AppState, user::AppUser};
+use super::{
+    error::AppError,
+    state::AppState,
+    user::{AppUser, Scope},
+};
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/vapid", get(get_vapid))
-        .route("/", post(create_push))
-        .route("/", get(get_pushes))
+        .route("/", post(create_push).layer(Extension(Scope::PushesWrite)))
+        .route("/", get(get_pushes).layer(Extension(Scope::PushesRead)))
 }
 
 async fn get_vapid(
@@ -55,12 +59,18 @@ mod get_vapid {
 async fn create_push(
     State(AppState {
         push_service_client,
+        sessions,
         ..
     }): State<AppState>,
     user: AppUser,
     Json(req): Json<create_push::Request>,
 ) -> Result<Json<create_push::Response>, AppError> {
-    dbg!(&req);
+    // Lets the subscription be removed together with its session
+    if let Some(jti) = user.jti {
+        sessions
+            .set_device(user.id, jti, req.device_id.clone())
+            .await?;
+    }
 
     let res = push_service_client
         .clone()
@@ -74,8 +84,6 @@ async fn create_push(
         .await?
         .into_inner();
 
-    dbg!(&res);
-
     Ok(Json(res.into()))
 }
 
@@ -0,0 +1,27 @@
+use std::{future::Future, time::Duration};
+
+use tokio::time;
+
+use super::error::AppError;
+
+// Bounds a backend call in milliseconds, so one slow service cannot stall a whole request
+pub async fn deadline<T>(
+    deadline: u64,
+    call: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+) -> Result<T, AppError> {
+    match time::timeout(Duration::from_millis(deadline), call).await {
+        Ok(res) => Ok(res?.into_inner()),
+        Err(_) => Err(tonic::Status::deadline_exceeded("rpc: deadline exceeded").into()),
+    }
+}
+
+// Bounds all backend calls of a request together, sequential calls share the same deadline
+pub async fn budget<T>(
+    deadline: u64,
+    calls: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    match time::timeout(Duration::from_millis(deadline), calls).await {
+        Ok(res) => res,
+        Err(_) => Err(tonic::Status::deadline_exceeded("rpc: budget exceeded").into()),
+    }
+}
@@ -4,7 +4,13 @@ use config::{Config, ConfigError, Environment, File};
 use flux_lib::settings::{HttpSettings, NATSSettings};
 use serde::Deserialize;
 
-use super::{auth::settings::AuthSettings, notify::settings::NotifySettings};
+use super::{
+    admin::settings::AdminSettings, attachments::settings::AttachmentsSettings,
+    auth::settings::AuthSettings, hydration::settings::HydrationSettings,
+    markdown::settings::MarkdownSettings, messages::settings::MessagesSettings,
+    notify::settings::NotifySettings, streams::settings::StreamsSettings,
+    users::settings::UsersSettings,
+};
 
 #[derive(Deserialize, Clone)]
 pub struct AppSettings {
@@ -12,12 +18,20 @@ pub struct AppSettings {
     pub http: HttpSettings,
     pub auth: AuthSettings,
     pub clients: ClientsSettings,
+    pub messages: MessagesSettings,
+    pub streams: StreamsSettings,
+    pub attachments: AttachmentsSettings,
     pub notify: NotifySettings,
+    pub hydration: HydrationSettings,
+    pub markdown: MarkdownSettings,
+    pub users: UsersSettings,
+    pub admin: AdminSettings,
     pub nats: NATSSettings,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct ClientsSettings {
+    pub deadline: u64,
     pub flux_users: ClientSettings,
     pub flux_messages: ClientSettings,
     pub flux_notify: ClientSettings,
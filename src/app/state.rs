@@ -9,10 +9,22 @@ use flux_notify_api::push_service_client::PushServiceClient;
 use flux_users_api::{
     auth_service_client::AuthServiceClient, users_service_client::UsersServiceClient,
 };
-use tokio::fs;
 use tonic::transport::Channel;
 
-use super::{notify::state::NotifyState, settings::AppSettings, AppJS};
+use super::{
+    admin::AdminImports,
+    attachments::state::AttachmentsState,
+    auth::{
+        denylist::AuthDenylist, jobs::AuthJobs, keys::AuthKeys, sessions::AuthSessions,
+        tokens::AuthTokens,
+    },
+    hydration::state::HydrationState,
+    markdown::MarkdownState,
+    messages::{idempotency::MessagesIdempotency, search::MessagesSearch},
+    notify::state::NotifyState,
+    settings::AppSettings,
+    AppJS,
+};
 
 #[derive(Clone)]
 pub struct AppState {
@@ -22,7 +34,17 @@ pub struct AppState {
     pub streams_service_client: StreamsServiceClient<Channel>,
     pub messages_service_client: MessagesServiceClient<Channel>,
     pub push_service_client: PushServiceClient<Channel>,
-    pub public_key: Vec<u8>,
+    pub keys: AuthKeys,
+    pub denylist: AuthDenylist,
+    pub tokens: AuthTokens,
+    pub jobs: AuthJobs,
+    pub sessions: AuthSessions,
+    pub attachments: AttachmentsState,
+    pub hydration: HydrationState,
+    pub markdown: MarkdownState,
+    pub idempotency: MessagesIdempotency,
+    pub search: MessagesSearch,
+    pub imports: AdminImports,
     pub notify: NotifyState,
     pub js: Arc<AppJS>,
 }
@@ -49,9 +71,21 @@ impl AppState {
         let push_service_client =
             Self::push_service_client(settings.clients.flux_notify.endpoint.clone()).await?;
 
-        let public_key = fs::read_to_string(&settings.auth.public_key_file)
-            .await?
-            .into_bytes();
+        let keys = AuthKeys::new(&settings.auth).await?;
+        let denylist = AuthDenylist::new(&js, &settings.auth.denylist).await?;
+        let tokens = AuthTokens::new(&js, &settings.auth.tokens).await?;
+        let jobs = AuthJobs::new(&js, &settings.auth.jobs).await?;
+        let sessions = AuthSessions::new(&js, &settings.auth.sessions).await?;
+        let attachments = AttachmentsState::new(&js, settings.attachments.clone()).await?;
+        let idempotency = MessagesIdempotency::new(&js, &settings.messages.idempotency).await?;
+        let search = MessagesSearch::new(&settings.messages.search)?;
+        let imports = AdminImports::new(&js, &settings.admin.import).await?;
+        let markdown = MarkdownState::new(&settings.markdown);
+        let hydration = HydrationState::new(
+            &settings,
+            users_service_client.clone(),
+            streams_service_client.clone(),
+        );
 
         Ok(Self {
             settings,
@@ -60,7 +94,17 @@ impl AppState {
             streams_service_client,
             messages_service_client,
             push_service_client,
-            public_key,
+            keys,
+            denylist,
+            tokens,
+            jobs,
+            sessions,
+            attachments,
+            hydration,
+            markdown,
+            idempotency,
+            search,
+            imports,
             notify,
             js,
         })
@@ -72,7 +116,9 @@ impl AppState {
         Ok(AuthServiceClient::new(ch))
     }
 
-    async fn users_service_client(dst: String) -> Result<UsersServiceClient<Channel>, Error> {
+    pub(super) async fn users_service_client(
+        dst: String,
+    ) -> Result<UsersServiceClient<Channel>, Error> {
         let ch = tonic::transport::Endpoint::new(dst)?.connect_lazy();
 
         Ok(UsersServiceClient::new(ch))
@@ -84,7 +130,9 @@ impl AppState {
         Ok(StreamsServiceClient::new(ch))
     }
 
-    async fn messages_service_client(dst: String) -> Result<MessagesServiceClient<Channel>, Error> {
+    pub(super) async fn messages_service_client(
+        dst: String,
+    ) -> Result<MessagesServiceClient<Channel>, Error> {
         let ch = tonic::transport::Endpoint::new(dst)?.connect_lazy();
 
         Ok(MessagesServiceClient::new(ch))
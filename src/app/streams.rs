@@ -1,278 +1,303 @@
-use axum::{extract::State, routing::get, Json, Router};
-use flux_messages_api::GetUserStreamsRequest;
-use get_last_streams::Response;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use flux_messages_api::{GetStreamsRequest, GetUserStreamsRequest};
+use sha2::{Digest as _, Sha256};
+use uuid::Uuid;
 
 use crate::app::locale::AppLocale;
 
-use super::{error::AppError, state::AppState, user::AppUser};
+use super::{
+    error::AppError,
+    messages::{fetch_messages, BatchError},
+    pagination::{Cursors, Pagination},
+    rpc,
+    state::AppState,
+    user::{AppUser, Scope},
+};
+
+pub(super) mod settings;
 
 pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(get_last_streams))
-        .route("/my", get(get_user_streams))
+        .route(
+            "/my",
+            get(get_user_streams).layer(Extension(Scope::StreamsRead)),
+        )
+        .route(
+            "/{stream_id}",
+            get(get_stream).layer(Extension(Scope::StreamsRead)),
+        )
 }
 
-// TODO: make requests async
-
 async fn get_last_streams(
     State(AppState {
         streams_service_client,
-        users_service_client,
+        hydration,
+        settings,
         ..
     }): State<AppState>,
     locale: AppLocale,
-) -> Result<Json<Response>, AppError> {
-    let get_last_streams_response = streams_service_client
-        .clone()
-        .get_last_streams(flux_messages_api::GetLastStreamsRequest {
-            locale: Some(locale.to_string()),
-        })
-        .await?
-        .into_inner();
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<get_last_streams::Response>, AppError> {
+    let deadline = settings.clients.deadline;
+    let limit = pagination.limit(&settings.streams.pagination)?;
+
+    rpc::budget(deadline, async {
+        let get_last_streams_response = rpc::deadline(
+            deadline,
+            streams_service_client.clone().get_last_streams(
+                flux_messages_api::GetLastStreamsRequest {
+                    locale: Some(locale.to_string()),
+                    limit: Some(limit),
+                    before_stream_id: pagination.before.map(|v| v.into()),
+                    after_stream_id: pagination.after.map(|v| v.into()),
+                },
+            ),
+        )
+        .await?;
 
-    let get_streams_response = streams_service_client
-        .clone()
-        .get_streams(flux_messages_api::GetStreamsRequest {
-            stream_ids: get_last_streams_response.stream_ids,
-        })
-        .await?
-        .into_inner();
-
-    let get_users_response = users_service_client
-        .clone()
-        .get_users(flux_users_api::GetUsersRequest {
-            user_ids: get_streams_response
-                .streams
-                .iter()
-                .map(|m| m.user_ids.clone())
-                .flatten()
-                .collect(),
-        })
-        .await?
-        .into_inner();
+        let cursors = Cursors {
+            next: get_last_streams_response.next_stream_id,
+            prev: get_last_streams_response.prev_stream_id,
+        };
 
-    Ok(Json((get_streams_response, get_users_response).try_into()?))
+        let streams = hydration
+            .streams(get_last_streams_response.stream_ids)
+            .await?;
+
+        Ok(Json(get_last_streams::Response { streams, cursors }))
+    })
+    .await
 }
 
 mod get_last_streams {
-    use std::collections::HashMap;
-
-    use flux_users_api::get_users_response;
     use serde::Serialize;
 
-    use crate::app::error::AppError;
+    use crate::app::{hydration::Stream, pagination::Cursors};
 
     #[derive(Serialize)]
     pub struct Response {
-        streams: Vec<Stream>,
+        pub streams: Vec<Stream>,
+        #[serde(flatten)]
+        pub cursors: Cursors,
     }
+}
 
-    #[derive(Serialize)]
-    struct Stream {
-        stream_id: String,
-        message_id: String,
-        text: Option<String>,
-        users: Vec<User>,
-    }
-
-    #[derive(Serialize)]
-    struct User {
-        user_id: String,
-        name: String,
-        first_name: String,
-        last_name: String,
-        abbr: String,
-        color: String,
-    }
-
-    impl
-        TryFrom<(
-            flux_messages_api::GetStreamsResponse,
-            flux_users_api::GetUsersResponse,
-        )> for Response
-    {
-        type Error = AppError;
-
-        fn try_from(
-            (get_streams_response, get_users_response): (
-                flux_messages_api::GetStreamsResponse,
-                flux_users_api::GetUsersResponse,
-            ),
-        ) -> Result<Self, Self::Error> {
-            let users: HashMap<String, get_users_response::User> = get_users_response
-                .users
-                .into_iter()
-                .map(|v| (v.user_id().into(), v))
-                .collect();
-
-            Ok(Self {
-                streams: get_streams_response
-                    .streams
-                    .iter()
-                    .map(|m| -> Result<Stream, Self::Error> {
-                        Ok(Stream {
-                            stream_id: m.stream_id().into(),
-                            message_id: m.message_id().into(),
-                            text: m.text.clone(),
-                            users: m
-                                .user_ids
-                                .iter()
-                                .map(|user_id| -> Result<User, Self::Error> {
-                                    users.get(user_id).try_into()
-                                })
-                                .collect::<Result<Vec<User>, Self::Error>>()?,
-                        })
-                    })
-                    .collect::<Result<Vec<Stream>, Self::Error>>()?,
-            })
-        }
-    }
+async fn get_user_streams(
+    State(AppState {
+        streams_service_client,
+        hydration,
+        settings,
+        ..
+    }): State<AppState>,
+    user: AppUser,
+    Query(pagination): Query<Pagination>,
+) -> Result<Json<get_user_streams::Response>, AppError> {
+    let deadline = settings.clients.deadline;
+    let limit = pagination.limit(&settings.streams.pagination)?;
+
+    rpc::budget(deadline, async {
+        let get_user_streams_response = rpc::deadline(
+            deadline,
+            streams_service_client
+                .clone()
+                .get_user_streams(GetUserStreamsRequest {
+                    user_id: Some(user.id.into()),
+                    limit: Some(limit),
+                    before_stream_id: pagination.before.map(|v| v.into()),
+                    after_stream_id: pagination.after.map(|v| v.into()),
+                }),
+        )
+        .await?;
+
+        let cursors = Cursors {
+            next: get_user_streams_response.next_stream_id,
+            prev: get_user_streams_response.prev_stream_id,
+        };
+
+        let streams = hydration
+            .streams(get_user_streams_response.stream_ids)
+            .await?;
+
+        Ok(Json(get_user_streams::Response { streams, cursors }))
+    })
+    .await
+}
 
-    impl TryFrom<Option<&get_users_response::User>> for User {
-        type Error = AppError;
+mod get_user_streams {
+    use serde::Serialize;
 
-        fn try_from(user: Option<&get_users_response::User>) -> Result<Self, Self::Error> {
-            let user = user.ok_or(AppError::NoEntity)?.to_owned();
+    use crate::app::{hydration::Stream, pagination::Cursors};
 
-            Ok(Self {
-                user_id: user.user_id().into(),
-                name: user.name().into(),
-                first_name: user.first_name().into(),
-                last_name: user.last_name().into(),
-                abbr: user.abbr().into(),
-                color: user.color().into(),
-            })
-        }
+    #[derive(Serialize)]
+    pub struct Response {
+        pub streams: Vec<Stream>,
+        #[serde(flatten)]
+        pub cursors: Cursors,
     }
 }
 
-async fn get_user_streams(
+async fn get_stream(
+    Path(stream_id): Path<Uuid>,
     State(AppState {
         streams_service_client,
-        users_service_client,
+        messages_service_client,
+        hydration,
+        settings,
         ..
     }): State<AppState>,
-    user: AppUser,
-) -> Result<Json<get_user_streams::Res>, AppError> {
-    let get_user_streams_response = streams_service_client
-        .clone()
-        .get_user_streams(GetUserStreamsRequest {
-            user_id: Some(user.id.into()),
-        })
+    user: Option<AppUser>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let deadline = settings.clients.deadline;
+
+    let (stream, root, latest, users) = rpc::budget(deadline, async {
+        let stream = rpc::deadline(
+            deadline,
+            streams_service_client
+                .clone()
+                .get_streams(GetStreamsRequest {
+                    stream_ids: vec![stream_id.into()],
+                }),
+        )
         .await?
-        .into_inner();
+        .streams
+        .into_iter()
+        .next()
+        .ok_or(AppError::NoEntity)?;
+
+        let root_message_ids = [stream.message_id.clone().ok_or(AppError::NoEntity)?];
+
+        let (root, latest) = tokio::join!(
+            fetch_messages(
+                &messages_service_client,
+                deadline,
+                user.as_ref(),
+                &root_message_ids,
+            ),
+            fetch_messages(
+                &messages_service_client,
+                deadline,
+                user.as_ref(),
+                stream.last_message_id.as_slice(),
+            ),
+        );
+
+        let root = root.into_iter().next().ok_or(AppError::NoEntity)??;
+
+        // The latest message can be deleted after the stream was read
+        let latest = match latest.into_iter().next() {
+            Some(Err(err)) if BatchError::of(&err) == Some(BatchError::NotFound) => None,
+            res => res.transpose()?,
+        };
+
+        let user_ids: Vec<String> = stream
+            .user_ids
+            .iter()
+            .chain(root.user_id.iter())
+            .chain(latest.iter().filter_map(|message| message.user_id.as_ref()))
+            .cloned()
+            .collect();
+
+        let users = hydration.users(&user_ids).await?;
+
+        Ok((stream, root, latest, users))
+    })
+    .await?;
+    let messages_count = stream.messages_count();
+    let preview_len = settings.streams.preview_len;
+
+    let res = get_stream::Response {
+        stream: (stream, &users).try_into()?,
+        messages_count,
+        root: (root, &users, None).try_into()?,
+        latest: latest
+            .map(|message| (message, &users, Some(preview_len)).try_into())
+            .transpose()?,
+    };
+
+    // The tag is derived from the body, so any change in the stream, its users or messages
+    // produces a new one
+    let body = serde_json::to_vec(&res)?;
+    let etag = format!("\"{}\"", hex::encode(&Sha256::digest(&body)[..16]));
+
+    let matched = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.trim_start_matches("W/") == etag
+            })
+        });
 
-    let get_streams_response = streams_service_client
-        .clone()
-        .get_streams(flux_messages_api::GetStreamsRequest {
-            stream_ids: get_user_streams_response.stream_ids,
-        })
-        .await?
-        .into_inner();
-
-    let get_users_response = users_service_client
-        .clone()
-        .get_users(flux_users_api::GetUsersRequest {
-            user_ids: get_streams_response
-                .streams
-                .iter()
-                .map(|m| m.user_ids.clone())
-                .flatten()
-                .collect(),
-        })
-        .await?
-        .into_inner();
+    if matched {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
 
-    Ok(Json((get_streams_response, get_users_response).try_into()?))
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::ETAG, etag),
+        ],
+        body,
+    )
+        .into_response())
 }
 
-mod get_user_streams {
-    use std::collections::HashMap;
-
-    use flux_users_api::get_users_response;
+mod get_stream {
+    use flux_messages_api::get_message_response;
     use serde::Serialize;
 
-    use crate::app::error::AppError;
+    use crate::app::{
+        error::AppError,
+        hydration::{Stream, User, Users},
+    };
 
     #[derive(Serialize)]
-    pub struct Res {
-        streams: Vec<Stream>,
+    pub struct Response {
+        pub stream: Stream,
+        pub messages_count: i64,
+        pub root: Message,
+        pub latest: Option<Message>,
     }
 
     #[derive(Serialize)]
-    pub struct Stream {
-        stream_id: String,
+    pub struct Message {
         message_id: String,
-        text: Option<String>,
-        users: Vec<User>,
+        text: String,
+        user: User,
+        order: i64,
     }
 
-    #[derive(Serialize)]
-    pub struct User {
-        user_id: String,
-        name: String,
-        first_name: String,
-        last_name: String,
-        abbr: String,
-        color: String,
-    }
-
-    impl
-        TryFrom<(
-            flux_messages_api::GetStreamsResponse,
-            flux_users_api::GetUsersResponse,
-        )> for Res
-    {
+    // Text is cut to the given number of characters for previews
+    impl TryFrom<(get_message_response::Message, &Users, Option<usize>)> for Message {
         type Error = AppError;
 
         fn try_from(
-            (get_streams_response, get_users_response): (
-                flux_messages_api::GetStreamsResponse,
-                flux_users_api::GetUsersResponse,
-            ),
+            (message, users, len): (get_message_response::Message, &Users, Option<usize>),
         ) -> Result<Self, Self::Error> {
-            let users: HashMap<String, get_users_response::User> = get_users_response
-                .users
-                .into_iter()
-                .map(|v| (v.user_id().into(), v))
-                .collect();
-
-            Ok(Self {
-                streams: get_streams_response
-                    .streams
-                    .iter()
-                    .map(|m| -> Result<Stream, Self::Error> {
-                        Ok(Stream {
-                            stream_id: m.stream_id().into(),
-                            message_id: m.message_id().into(),
-                            text: m.text.clone(),
-                            users: m
-                                .user_ids
-                                .iter()
-                                .map(|user_id| -> Result<User, Self::Error> {
-                                    users.get(user_id).try_into()
-                                })
-                                .collect::<Result<Vec<User>, Self::Error>>()?,
-                        })
-                    })
-                    .collect::<Result<Vec<Stream>, Self::Error>>()?,
-            })
-        }
-    }
-
-    impl TryFrom<Option<&get_users_response::User>> for User {
-        type Error = AppError;
-
-        fn try_from(user: Option<&get_users_response::User>) -> Result<Self, Self::Error> {
-            let user = user.ok_or(AppError::NoEntity)?.to_owned();
+            let text = match len {
+                Some(len) if message.text().chars().count() > len => {
+                    format!("{}…", message.text().chars().take(len).collect::<String>())
+                }
+                _ => message.text().into(),
+            };
 
             Ok(Self {
-                user_id: user.user_id().into(),
-                name: user.name().into(),
-                first_name: user.first_name().into(),
-                last_name: user.last_name().into(),
-                abbr: user.abbr().into(),
-                color: user.color().into(),
+                message_id: message.message_id().into(),
+                text,
+                user: users
+                    .get(message.user_id())
+                    .cloned()
+                    .ok_or(AppError::NoEntity)?,
+                order: message.order(),
             })
         }
     }
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+use crate::app::pagination::PaginationSettings;
+
+#[derive(Deserialize, Clone)]
+pub struct StreamsSettings {
+    pub pagination: PaginationSettings,
+    pub preview_len: usize,
+}
@@ -8,11 +8,22 @@ use axum_extra::{
     TypedHeader,
 };
 use flux_lib::error::Error;
-use jsonwebtoken::{decode, Algorithm, DecodingKey, TokenData, Validation};
-use serde::Deserialize;
+use jsonwebtoken::{decode, decode_header, TokenData, Validation};
+use serde::{Deserialize, Serialize};
+use tracing::error;
 use uuid::Uuid;
 
-use super::{error::AppError, state::AppState};
+use super::{
+    auth::{
+        cookie,
+        denylist::AuthDenylist,
+        keys::AuthKeys,
+        sessions::Client,
+        tokens::{self, AuthTokens},
+    },
+    error::AppError,
+    state::AppState,
+};
 
 impl<S> FromRequestParts<S> for AppUser
 where
@@ -22,13 +33,39 @@ where
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let AppState { public_key, .. } = AppState::from_ref(state);
+        let AppState {
+            keys,
+            denylist,
+            tokens,
+            sessions,
+            settings,
+            ..
+        } = AppState::from_ref(state);
+
+        let token = match parts.extract::<TypedHeader<Authorization<Bearer>>>().await {
+            Ok(TypedHeader(Authorization(bearer))) => bearer.token().to_string(),
+            Err(err) => cookie::token(parts, &settings.auth.cookie)?.ok_or(err)?,
+        };
 
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await?;
+        let user = match token.strip_prefix(tokens::PREFIX) {
+            Some(token) => {
+                extract_api_user(token, &tokens, parts.extensions.get::<Scope>()).await?
+            }
+            None => extract_user(&token, &keys, &denylist).await?,
+        };
 
-        let user = extract_user(bearer, &public_key).await?;
+        if let Some(jti) = user.jti {
+            if let Err(err) = sessions
+                .touch(
+                    user.id,
+                    jti,
+                    Client::from_parts(parts, &settings.auth.sessions.trusted_proxies),
+                )
+                .await
+            {
+                error!("{}", err);
+            }
+        }
 
         Ok(user)
     }
@@ -45,30 +82,95 @@ where
         parts: &mut Parts,
         state: &S,
     ) -> Result<Option<Self>, Self::Rejection> {
+        // A token lacking the route's scope or a failed CSRF check is refused, not made anonymous
         match <AppUser as FromRequestParts<S>>::from_request_parts(parts, state).await {
             Ok(user) => Ok(Some(user)),
+            Err(AppError::Forbidden) => Err(AppError::Forbidden),
             Err(_) => Ok(None),
         }
     }
 }
 
-async fn extract_user(bearer: Bearer, public_key_file: &Vec<u8>) -> Result<AppUser, Error> {
-    let TokenData { claims, .. } = decode::<Claims>(
-        bearer.token(),
-        &DecodingKey::from_rsa_pem(public_key_file)?,
-        &Validation::new(Algorithm::RS256),
-    )?;
+async fn extract_user(
+    token: &str,
+    keys: &AuthKeys,
+    denylist: &AuthDenylist,
+) -> Result<AppUser, Error> {
+    let header = decode_header(token)?;
+    let key = keys
+        .get(header.kid.as_deref())
+        .await
+        .ok_or_else(|| Error::msg("auth: unknown key"))?;
+
+    if header.alg != key.algorithm {
+        return Err(Error::msg("auth: algorithm mismatch"));
+    }
 
-    Ok(AppUser { id: claims.sub })
+    let TokenData { claims, .. } =
+        decode::<Claims>(token, &key.decoding_key, &Validation::new(key.algorithm))?;
+
+    if denylist.is_user_revoked(claims.sub).await? {
+        return Err(Error::msg("auth: user revoked"));
+    }
+
+    if let Some(jti) = claims.jti {
+        if denylist.is_revoked(jti).await? {
+            return Err(Error::msg("auth: token revoked"));
+        }
+    }
+
+    Ok(AppUser {
+        id: claims.sub,
+        jti: claims.jti,
+    })
+}
+
+// API tokens are only accepted by routes that declare a scope via `Extension<Scope>`
+async fn extract_api_user(
+    token: &str,
+    tokens: &AuthTokens,
+    scope: Option<&Scope>,
+) -> Result<AppUser, AppError> {
+    let api_token = tokens.verify(token).await?;
+
+    match scope {
+        Some(scope) if api_token.scopes.contains(scope) => Ok(AppUser {
+            id: api_token.user_id,
+            jti: None,
+        }),
+        _ => Err(AppError::Forbidden),
+    }
 }
 
 #[derive(Deserialize)]
 pub struct AppUser {
     pub id: Uuid,
+    pub jti: Option<Uuid>,
 }
 
 #[derive(Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
+    pub jti: Option<Uuid>,
     // pub exp: usize,
 }
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scope {
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "streams:read")]
+    StreamsRead,
+    #[serde(rename = "messages:read")]
+    MessagesRead,
+    #[serde(rename = "messages:write")]
+    MessagesWrite,
+    #[serde(rename = "pushes:read")]
+    PushesRead,
+    #[serde(rename = "pushes:write")]
+    PushesWrite,
+}
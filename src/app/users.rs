@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Extension, Json, Router,
+};
+use flux_users_api::SearchUsersRequest;
+use uuid::Uuid;
+
+use super::{
+    error::AppError,
+    state::AppState,
+    user::{AppUser, Scope},
+};
+
+pub(super) mod settings;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(search_users).layer(Extension(Scope::UsersRead)))
+        .route("/{user_id}", get(get_user))
+}
+
+async fn get_user(
+    Path(user_id): Path<Uuid>,
+    State(AppState { hydration, .. }): State<AppState>,
+) -> Result<Json<get_user::Response>, AppError> {
+    let user = hydration.user(user_id).await?;
+
+    Ok(Json(get_user::Response { user }))
+}
+
+mod get_user {
+    use serde::Serialize;
+
+    use crate::app::hydration::User;
+
+    #[derive(Serialize)]
+    pub struct Response {
+        pub user: User,
+    }
+}
+
+async fn search_users(
+    State(AppState {
+        users_service_client,
+        hydration,
+        settings,
+        ..
+    }): State<AppState>,
+    _: AppUser,
+    Query(req): Query<search_users::Request>,
+) -> Result<Json<search_users::Response>, AppError> {
+    let search_users_response = users_service_client
+        .clone()
+        .search_users(SearchUsersRequest {
+            query: req.q.map(|q| q.trim().into()),
+            limit: Some(
+                req.limit
+                    .unwrap_or(settings.users.limit)
+                    .clamp(1, settings.users.max_limit),
+            ),
+            cursor_user_id: req.cursor_user_id.map(|v| v.into()),
+        })
+        .await?
+        .into_inner();
+
+    let users = hydration.users(&search_users_response.user_ids).await?;
+
+    Ok(Json((search_users_response, users).try_into()?))
+}
+
+mod search_users {
+    use flux_users_api::SearchUsersResponse;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::app::{
+        error::AppError,
+        hydration::{User, Users},
+    };
+
+    #[derive(Deserialize, Debug)]
+    pub struct Request {
+        pub q: Option<String>,
+        pub limit: Option<i64>,
+        pub cursor_user_id: Option<Uuid>,
+    }
+
+    #[derive(Serialize)]
+    pub struct Response {
+        users: Vec<User>,
+        cursor_user_id: Option<String>,
+    }
+
+    impl TryFrom<(SearchUsersResponse, Users)> for Response {
+        type Error = AppError;
+
+        // Keeps the order of the search results, hydrated users are keyed by id
+        fn try_from(
+            (search_users_response, mut users): (SearchUsersResponse, Users),
+        ) -> Result<Self, Self::Error> {
+            Ok(Self {
+                users: search_users_response
+                    .user_ids
+                    .iter()
+                    .map(|user_id| users.remove(user_id).ok_or(AppError::NoEntity))
+                    .collect::<Result<Vec<User>, Self::Error>>()?,
+                cursor_user_id: search_users_response.cursor_user_id,
+            })
+        }
+    }
+}
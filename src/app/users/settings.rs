@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct UsersSettings {
+    pub limit: i64,
+    pub max_limit: i64,
+}
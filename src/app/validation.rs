@@ -0,0 +1,41 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::error::{AppError, FieldError};
+
+pub trait Validate {
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+pub fn parse<T>(value: Value) -> Result<T, AppError>
+where
+    T: DeserializeOwned + Validate,
+{
+    let data: T = deserialize(value)?;
+    check(&data)?;
+
+    Ok(data)
+}
+
+// For requests validated against settings, through a wrapper that holds both
+pub fn check(data: &impl Validate) -> Result<(), AppError> {
+    let errors = data.validate();
+    if !errors.is_empty() {
+        return Err(AppError::Validation(errors));
+    }
+
+    Ok(())
+}
+
+// For requests that are normalized before they are validated
+pub fn deserialize<T>(value: Value) -> Result<T, AppError>
+where
+    T: DeserializeOwned,
+{
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        AppError::Validation(vec![FieldError::new(
+            err.path().to_string(),
+            err.inner().to_string(),
+        )])
+    })
+}
@@ -1,3 +1,5 @@
+use std::env;
+
 use flux_lib::error::Error;
 
 mod app;
@@ -6,7 +8,12 @@ mod app;
 async fn main() -> Result<(), Error> {
     flux_lib::tracing::init()?;
 
-    app::run().await?;
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.split_first() {
+        Some((command, args)) if command == "import" => app::import(args).await?,
+        _ => app::run().await?,
+    }
 
     Ok(())
 }